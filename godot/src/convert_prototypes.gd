@@ -0,0 +1,33 @@
+# Converts a tileset to another format without running the game:
+#   godot --headless --path godot -s res://src/convert_prototypes.gd -- <source> <destination> [options]
+# The destination's extension picks the format: ".lwfc" for the compact binary rule set that
+# loads much faster than the exported JSON, ".toml" for a tileset whose rules come from its
+# sockets, and JSON otherwise. Options:
+#   --expand-rotations, --derive-adjacency: load the source the same way the driver would
+# Exits with 1 if the conversion failed.
+extends SceneTree
+
+
+func _init():
+	var driver = LWFCDriver.new()
+	var paths = []
+	for arg in OS.get_cmdline_user_args():
+		if arg == "--expand-rotations":
+			driver.expand_rotations = true
+		elif arg == "--derive-adjacency":
+			driver.derive_adjacency = true
+		else:
+			paths.append(arg)
+
+	if len(paths) != 2:
+		printerr("usage: convert_prototypes.gd -- <source> <destination> [options]")
+		driver.free()
+		quit(1)
+		return
+
+	driver.prototype_path = paths[0]
+	var converted = driver.convert_prototypes(paths[1])
+	driver.free()
+	if converted:
+		print("converted %s to %s" % [paths[0], paths[1]])
+	quit(0 if converted else 1)
@@ -4,56 +4,90 @@ extends Node3D
 @onready var cell_scene = preload("res://scenes/Cell.tscn")
 
 var cell_matrix: Array = []
+var map_origin := Vector3i.ZERO
 
 var changes_queued: Array = []
 
+# Values match LWFCDriver.set_chunk_order
+enum ChunkOrder { LINEAR, SPIRAL, NEAREST_TO_FOCUS }
+
+# Order the worker collapses chunks in. The spiral and nearest orders work outward from the camera.
+@export var chunk_order: ChunkOrder = ChunkOrder.NEAREST_TO_FOCUS
+
 const CELL_SIZE = 6
 const MAX_JITTER = 2
 
 
 func _ready():
-	$Area.mesh.size = driver.map_size
-	$Area.position = floor(Vector3(driver.map_size) / 2) - Vector3.ONE * 0.5
-
+	Preload.ProtoData = driver.get_prototype_data()
 	$CameraBase.position += Vector3(
-		driver.map_size.x * CELL_SIZE / 2,
+		(driver.map_origin.x + driver.map_size.x / 2) * CELL_SIZE,
 		0,
-		driver.map_size.z * CELL_SIZE / 2
+		(driver.map_origin.z + driver.map_size.z / 2) * CELL_SIZE
 	)
 
-	for y in range(driver.map_size.y):
+	build_cells(driver.map_origin, driver.map_size)
+	driver.set_chunk_order(chunk_order)
+	update_focus()
+	driver.start()
+
+
+# cell_matrix is indexed relative to the map origin, which may be negative
+func build_cells(origin: Vector3i, map_size: Vector3i):
+	map_origin = origin
+	$Area.mesh.size = map_size
+	$Area.position = Vector3(origin) + floor(Vector3(map_size) / 2) - Vector3.ONE * 0.5
+
+	for y in range(map_size.y):
 		cell_matrix.append([])
-		for x in range(driver.map_size.x):
+		for x in range(map_size.x):
 			cell_matrix[y].append([])
-			for z in range(driver.map_size.z):
+			for z in range(map_size.z):
 				var cell = cell_scene.instantiate()
-				cell.name = "Cell %d %d %d" % [x, y, z]
+				cell.name = "Cell %d %d %d" % [origin.x + x, origin.y + y, origin.z + z]
 				var jitter = Vector3(
 					randf_range(-MAX_JITTER, MAX_JITTER),
 					0,
 					randf_range(-MAX_JITTER, MAX_JITTER),
 				)
-				cell.position = CELL_SIZE * Vector3(x, y, z) + jitter
+				cell.position = CELL_SIZE * Vector3(origin + Vector3i(x, y, z)) + jitter
 				cell.get_node("Highlight").mesh.size = CELL_SIZE * Vector3.ONE
 				add_child(cell)
 				cell.owner = self
 				cell_matrix[y][x].append(cell)
 
-	driver.start()
+
+func clear_cells():
+	for plane in cell_matrix:
+		for row in plane:
+			for cell in row:
+				cell.queue_free()
+	cell_matrix = []
+	changes_queued = []
+
+
+func update_focus():
+	var camera_cell = $CameraBase.position / CELL_SIZE
+	driver.focus = Vector3i(camera_cell.x, 0, camera_cell.z)
 
 
 func _process(_delta):
+	update_focus()
 	for i in range(100):
 		if len(changes_queued) > 0:
 			var change = changes_queued.pop_front()
-			var change_position = change[0]
-			var cell = cell_matrix[change_position.y][change_position.x][change_position.z]
+			var cell = get_cell(change[0])
 			if cell:
 				cell.change(change[1])
 
 
+func get_cell(cell_position: Vector3i):
+	var local = cell_position - map_origin
+	return cell_matrix[local.y][local.x][local.z]
+
+
 func play_expand_animation(cell_position: Vector3, protos: Array):
-	var cell = cell_matrix[cell_position.y][cell_position.x][cell_position.z]
+	var cell = get_cell(Vector3i(cell_position))
 	if cell:
 		cell.expand(protos)
 	else:
@@ -76,3 +110,9 @@ func _on_cells_changed(changes):
 		var change_protos: String = change["new_protos"]
 		changes_queued.append([change_position, change_protos.split(",")])
 
+
+func _on_map_reset(origin: Vector3i, map_size: Vector3i):
+	# The prototypes may have been reloaded along with the map
+	Preload.ProtoData = driver.get_prototype_data()
+	clear_cells()
+	build_cells(origin, map_size)
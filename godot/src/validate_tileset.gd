@@ -0,0 +1,58 @@
+# Checks a tileset without running the game, for use in CI:
+#   godot --headless --path godot -s res://src/validate_tileset.gd -- [path] [options]
+# The path defaults to res://prototype_data.json. Options:
+#   --expand-rotations, --derive-adjacency: load the tileset the same way the driver would
+#   --analyze: also check whether the tileset can tile an unbounded map
+#   --vertical: analyze along the vertical axis too, for tilesets with more than one layer
+# Exits with 1 if any errors were found.
+extends SceneTree
+
+
+func _init():
+	var driver = LWFCDriver.new()
+	var analyze = false
+	var vertical = false
+	for arg in OS.get_cmdline_user_args():
+		if arg == "--expand-rotations":
+			driver.expand_rotations = true
+		elif arg == "--derive-adjacency":
+			driver.derive_adjacency = true
+		elif arg == "--analyze":
+			analyze = true
+		elif arg == "--vertical":
+			vertical = true
+		else:
+			driver.prototype_path = arg
+
+	var issues = driver.validate_tileset()
+	var report = driver.analyze_tileset(vertical) if analyze else {}
+	driver.free()
+
+	var errors = 0
+	for issue in issues:
+		print("%s: %s: %s" % [issue["severity"], issue["prototype"], issue["message"]])
+		if issue["severity"] == "error":
+			errors += 1
+	print("%d issues, %d errors" % [len(issues), errors])
+
+	if not report.is_empty():
+		print_analysis(report)
+
+	quit(1 if errors > 0 else 0)
+
+
+func print_analysis(report: Dictionary):
+	for eliminated in report["eliminated"]:
+		print("eliminated: %s: nothing left to fit on %s" % [eliminated["prototype"], eliminated["side"]])
+	print("%d prototypes always lead to a contradiction away from the map's edges" % len(report["eliminated"]))
+
+	if not report["periodic_tile"].is_empty():
+		print("tiles infinitely, repeating every %s:" % report["period"])
+		var positions = report["periodic_tile"].keys()
+		positions.sort()
+		for position in positions:
+			print("  %s: %s" % [position, report["periodic_tile"][position]])
+	elif report["search_complete"]:
+		print("no periodic tile found, the tileset can't tile infinitely with a small period")
+	else:
+		print("no periodic tile found before the search gave up")
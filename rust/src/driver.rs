@@ -1,19 +1,75 @@
-use std::sync::mpsc::{channel, Receiver, Sender};
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
+use godot::engine::{Image, Texture2D};
 use godot::prelude::*;
+use serde_json::{Map, Value};
 
-use crate::models::collapser_action::{CollapserAction, CollapserActionType};
+use crate::models::chunk_event::{ChunkEvent, ChunkEventKind};
+use crate::models::chunk_order::ChunkOrder;
+use crate::models::collapser_action::{CollapserAction, CollapserCommand};
+use crate::models::collapser_state::CollapserState;
 use crate::models::driver_update::DriverUpdate;
-use crate::worker::collapser::LWFCCollapser;
+use crate::models::map_config::{MapConfig, SolverMode, StorageKind};
+use crate::models::prototype::{Prototype, PrototypeFormat, RotationExpansion, SocketRules};
+use crate::models::prototype_edit;
+use crate::models::prototype_selector::PrototypeSelector;
+use crate::models::retry_policy::{Escalation, RetryPolicy};
+use crate::models::tag_constraint::{TagConstraint, TagRule};
+use crate::models::tileset_analysis::SolvabilityAnalysis;
+use crate::models::tileset_validation::{self, Severity, TilesetIssue};
+use crate::models::weight_field::{FieldSource, WeightLayer};
+use crate::worker::collapser::{run_worker, FocusSlot};
+
+// How long exit_tree waits for the worker to finish before giving up on it
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(2);
 
 #[derive(GodotClass)]
 #[class(base=Node3D)]
 pub struct LWFCDriver {
     // Thread I/O
-    _handle: Option<JoinHandle<()>>,
-    send_to_thread: Option<Sender<CollapserAction>>,
+    handle: Option<JoinHandle<()>>,
+    send_to_thread: Option<SyncSender<CollapserAction>>,
     recv_in_main: Option<Receiver<DriverUpdate>>,
+    focus_slot: FocusSlot,
+    next_request_id: u64,
+    // Commands that didn't fit in the channel yet, oldest first. Sent ahead of any newer ones.
+    unsent: VecDeque<CollapserAction>,
+    // Commands that will replace the map. Changes received before their reset are stale.
+    pending_resets: Vec<u64>,
+    // As last loaded from `prototype_path`, with any edits made since
+    prototypes: Vec<Prototype>,
+    // The prototypes the worker's map was built from, for the tag queries. Edits only show up here
+    //  once the map is rebuilt from them.
+    applied: Vec<Prototype>,
+    // Prototypes sent with SetPrototypes, by request id, that take over from `applied` when that
+    //  request's reset arrives
+    applying: Vec<(u64, Vec<Prototype>)>,
+    // Prototype id of every collapsed cell, as of the last cells_changed, for the tag queries
+    collapsed: HashMap<Vector3i, String>,
+
+    // Where the prototypes come from: a res:// or user:// path, a path on disk, or the prototype
+    //  JSON itself. Files may also be binary (".lwfc") or a TOML tileset (".toml").
+    #[export]
+    pub prototype_path: GodotString,
+
+    // Work out which prototypes fit together from their sockets, ignoring the exported
+    //  valid_neighbours lists. TOML tilesets always do, so this and `expand_rotations` don't
+    //  apply to them.
+    #[export]
+    pub derive_adjacency: bool,
+
+    // Treat each prototype as a base module at rotation 0, and generate its other rotations.
+    //  Applied before `derive_adjacency`.
+    #[export]
+    pub expand_rotations: bool,
+
+    // Position of the map's lowest corner. May be negative.
+    #[export]
+    pub map_origin: Vector3i,
 
     #[export]
     pub map_size: Vector3i,
@@ -21,8 +77,40 @@ pub struct LWFCDriver {
     #[export]
     pub chunk_size: Vector3i,
 
+    // Cells shared between neighbouring chunks, per axis
+    #[export]
+    pub chunk_overlap: Vector3i,
+
+    // Store cells in fixed-size blocks keyed by block coordinate rather than one flat array over
+    //  the map bounds
+    #[export]
+    pub block_storage: bool,
+
+    // Start from a valid map and re-solve it a block at a time, instead of collapsing chunks from
+    //  scratch. Slower, but contradictions never reach the map. The map starts out filled with
+    //  `fill_prototype`, or as a finished map loaded with load_map_as_fill.
+    #[export]
+    pub modify_in_blocks: bool,
+
+    // Prototype id that every cell starts out as when modifying in blocks. It has to be allowed
+    //  next to itself along every axis the map is more than one cell across.
+    #[export]
+    pub fill_prototype: GodotString,
+
+    // Capacity of the channels to and from the worker. When Godot falls behind, the worker merges
+    //  its pending changes instead of queueing more.
+    #[export]
+    pub channel_capacity: i32,
+
+    // Rate limit for the worker, useful for watching generation. Zero means unlimited.
     #[export]
-    pub chunk_overlap: i32,
+    pub collapses_per_second: f64,
+    sent_collapses_per_second: f64,
+
+    // Cell that the Spiral and NearestToFocus chunk orders work outward from, e.g. under the camera
+    #[export]
+    pub focus: Vector3i,
+    sent_focus: Vector3i,
 
     #[base]
     node: Base<Node3D>,
@@ -32,47 +120,85 @@ pub struct LWFCDriver {
 impl INode3D for LWFCDriver {
     fn init(node: Base<Node3D>) -> Self {
         LWFCDriver {
-            _handle: None,
+            handle: None,
             send_to_thread: None,
             recv_in_main: None,
+            focus_slot: Arc::new(Mutex::new(None)),
+            next_request_id: 1,
+            unsent: VecDeque::new(),
+            pending_resets: vec![],
+            prototypes: vec![],
+            applied: vec![],
+            applying: vec![],
+            collapsed: HashMap::new(),
+            prototype_path: "res://prototype_data.json".into(),
+            derive_adjacency: false,
+            expand_rotations: false,
+            map_origin: Vector3i::ZERO,
             map_size: Vector3i { x: 15, y: 1, z: 15 },
             chunk_size: Vector3i { x: 9, y: 1, z: 9 },
-            chunk_overlap: 2,
+            chunk_overlap: Vector3i { x: 2, y: 2, z: 2 },
+            block_storage: false,
+            modify_in_blocks: false,
+            fill_prototype: "".into(),
+            channel_capacity: 64,
+            collapses_per_second: 0.0,
+            sent_collapses_per_second: 0.0,
+            focus: Vector3i::ZERO,
+            sent_focus: Vector3i::ZERO,
             node,
         }
     }
 
     fn ready(&mut self) {
-        let (send_to_thread, recv_in_thread) = channel::<CollapserAction>();
-        let (send_to_main, recv_in_main) = channel::<DriverUpdate>();
+        let prototypes = match self.load_prototypes() {
+            Ok(prototypes) => prototypes,
+            Err(message) => {
+                godot_error!("Failed to load prototypes: {}", message);
+                self.node.emit_signal(
+                    "worker_failed".into(),
+                    &[GodotString::from(message).to_variant()],
+                );
+                return;
+            }
+        };
+
+        self.applied = prototypes.clone();
+
+        let capacity = self.channel_capacity.max(1) as usize;
+        let (send_to_thread, recv_in_thread) = sync_channel::<CollapserAction>(capacity);
+        let (send_to_main, recv_in_main) = sync_channel::<DriverUpdate>(capacity);
 
         self.send_to_thread = Some(send_to_thread);
         self.recv_in_main = Some(recv_in_main);
 
-        let map_size = self.map_size.clone();
-        let chunk_size = self.chunk_size.clone();
-        let chunk_overlap = self.chunk_overlap.clone();
-
-        let _handle = thread::spawn(move || {
-            let mut collapser = LWFCCollapser::new(
-                send_to_main,
-                recv_in_thread,
-                map_size,
-                chunk_size,
-                chunk_overlap,
-            );
-            collapser.run()
-        });
+        let config = self.map_config(
+            self.map_origin,
+            self.map_size,
+            self.chunk_size,
+            self.chunk_overlap,
+        );
+
+        let focus = self.focus_slot.clone();
+        self.handle = Some(thread::spawn(move || {
+            run_worker(send_to_main, recv_in_thread, focus, config, prototypes)
+        }));
+
+        self.sync_exports();
     }
 
     fn process(&mut self, delta: f64) {
+        if let Err(message) = self.send_unsent() {
+            godot_error!("Failed to send action! {}", message);
+        }
+        self.sync_exports();
         for _ in 0..1 {
             self.tick(delta);
         }
     }
 
     fn exit_tree(&mut self) {
-        self.stop()
+        self.shutdown();
     }
 }
 
@@ -87,23 +213,640 @@ impl LWFCDriver {
     #[signal]
     fn cells_changed(changes: Array<Dictionary>);
 
+    // Emitted when the worker moves on to a new chunk
+    #[signal]
+    fn chunk_started(position: Vector3i, size: Vector3i);
+
+    // Emitted once a chunk's overlap has been reset and its seams with finished chunks propagated
+    #[signal]
+    fn chunk_seams_propagated(position: Vector3i, size: Vector3i);
+
+    // Emitted when a chunk has no cells left to collapse. `stats` has the keys "failed",
+    //  "collapses", "cells_changed", "contradictions", "seam_violations", "attempts" and
+    //  "elapsed_ms".
+    #[signal]
+    fn chunk_completed(position: Vector3i, size: Vector3i, stats: Dictionary);
+
+    // Emitted instead of chunk_completed when a failed chunk's cells were put back so it can be
+    //  tried again. `stats` is as for chunk_completed.
+    #[signal]
+    fn chunk_retried(position: Vector3i, size: Vector3i, stats: Dictionary);
+
+    // Emitted just before chunk_completed for each cell of the chunk that doesn't fit its neighbour
+    //  in an earlier chunk. If `repairing` is set, a block around the pair has been queued to be
+    //  collapsed again.
+    #[signal]
+    fn seam_violation(position: Vector3i, neighbor: Vector3i, repairing: bool);
+
+    // Emitted when the worker replaces its map. Any cells built for the old map should be
+    //  discarded.
+    #[signal]
+    fn map_reset(map_origin: Vector3i, map_size: Vector3i);
+
+    // Emitted if the prototypes can't be loaded, or if the worker thread panics or can't build its
+    //  initial map.
+    // The driver won't produce any more updates after this.
+    #[signal]
+    fn worker_failed(message: GodotString);
+
+    // Emitted once per command sent through this driver. `error` is empty on success.
+    #[signal]
+    fn command_completed(request_id: i64, error: GodotString);
+
+    // Every function below that sends a command to the worker returns that command's request id,
+    // or -1 if it couldn't be sent. Commands that don't fit in the channel are held back and sent
+    // on a later frame, in order, rather than dropped.
+
+    #[func]
+    pub fn start(&mut self) -> i64 {
+        self.send_command(CollapserCommand::Start)
+    }
+
+    #[func]
+    pub fn pause(&mut self) -> i64 {
+        self.send_command(CollapserCommand::Pause)
+    }
+
+    #[func]
+    pub fn stop(&mut self) -> i64 {
+        self.send_command(CollapserCommand::Stop)
+    }
+
+    #[func]
+    pub fn step(&mut self, count: i64) -> i64 {
+        self.send_command(CollapserCommand::Step {
+            count: count.max(0) as u32,
+        })
+    }
+
+    #[func]
+    pub fn regenerate(&mut self, seed: i64) -> i64 {
+        self.send_reset_command(CollapserCommand::Reset { seed: seed as u64 })
+    }
+
+    // Uses the current `block_storage` and `modify_in_blocks` settings. The exported sizes are
+    //  updated once the worker has actually rebuilt the map.
+    #[func]
+    pub fn resize(
+        &mut self,
+        map_origin: Vector3i,
+        map_size: Vector3i,
+        chunk_size: Vector3i,
+        chunk_overlap: Vector3i,
+    ) -> i64 {
+        let config = self.map_config(map_origin, map_size, chunk_size, chunk_overlap);
+        self.send_reset_command(CollapserCommand::Resize { config })
+    }
+
+    // Takes a dictionary of cell position (Vector3i) to prototype id
+    #[func]
+    pub fn pin_cells(&mut self, pins: Dictionary) -> i64 {
+        let mut cells = vec![];
+        for (key, value) in pins.iter_shared() {
+            match (key.try_to::<Vector3i>(), value.try_to::<GodotString>()) {
+                (Ok(position), Ok(id)) => cells.push((position, id.to_string())),
+                _ => godot_error!("Skipping invalid pin {:?}: {:?}", key, value),
+            }
+        }
+        self.send_command(CollapserCommand::PinCells { cells })
+    }
+
+    #[func]
+    pub fn regenerate_region(&mut self, position: Vector3i, size: Vector3i) -> i64 {
+        self.send_command(CollapserCommand::RegenerateRegion { position, size })
+    }
+
+    // Takes a dictionary of prototype id to weight
+    #[func]
+    pub fn set_weights(&mut self, weights: Dictionary) -> i64 {
+        let weights = parse_weights(weights, PrototypeSelector::Id);
+        self.send_command(CollapserCommand::SetWeights { weights })
+    }
+
+    // Takes a dictionary of mesh name to weight, applied to every rotation of the mesh
+    #[func]
+    pub fn set_mesh_weights(&mut self, weights: Dictionary) -> i64 {
+        let weights = parse_weights(weights, PrototypeSelector::Mesh);
+        self.send_command(CollapserCommand::SetWeights { weights })
+    }
+
+    // Disabled prototypes are removed from cells that haven't collapsed yet, and won't be placed
+    //  again until they're enabled
+    #[func]
+    pub fn set_prototypes_enabled(&mut self, ids: PackedStringArray, enabled: bool) -> i64 {
+        let selectors = parse_selectors(ids, PrototypeSelector::Id);
+        self.send_command(CollapserCommand::SetEnabled { selectors, enabled })
+    }
+
+    #[func]
+    pub fn set_meshes_enabled(&mut self, mesh_names: PackedStringArray, enabled: bool) -> i64 {
+        let selectors = parse_selectors(mesh_names, PrototypeSelector::Mesh);
+        self.send_command(CollapserCommand::SetEnabled { selectors, enabled })
+    }
+
+    // Takes a dictionary of tag to weight. Where a prototype has several of the tags, the last one
+    //  in the dictionary wins.
+    #[func]
+    pub fn set_tag_weights(&mut self, weights: Dictionary) -> i64 {
+        let weights = parse_weights(weights, PrototypeSelector::Tag);
+        self.send_command(CollapserCommand::SetWeights { weights })
+    }
+
+    #[func]
+    pub fn set_tags_enabled(&mut self, tags: PackedStringArray, enabled: bool) -> i64 {
+        let selectors = parse_selectors(tags, PrototypeSelector::Tag);
+        self.send_command(CollapserCommand::SetEnabled { selectors, enabled })
+    }
+
+    // Only prototypes with one of the tags may appear in the region from now on
+    #[func]
+    pub fn allow_only_tags(
+        &mut self,
+        position: Vector3i,
+        size: Vector3i,
+        tags: PackedStringArray,
+    ) -> i64 {
+        let rule = TagRule::Only(parse_tags(tags));
+        self.send_command(CollapserCommand::AddTagConstraint {
+            constraint: TagConstraint {
+                position,
+                size,
+                rule,
+            },
+        })
+    }
+
+    // Prototypes with any of the tags may not appear in the region from now on
+    #[func]
+    pub fn forbid_tags(
+        &mut self,
+        position: Vector3i,
+        size: Vector3i,
+        tags: PackedStringArray,
+    ) -> i64 {
+        let rule = TagRule::Exclude(parse_tags(tags));
+        self.send_command(CollapserCommand::AddTagConstraint {
+            constraint: TagConstraint {
+                position,
+                size,
+                rule,
+            },
+        })
+    }
+
+    #[func]
+    pub fn clear_tag_constraints(&mut self) -> i64 {
+        self.send_command(CollapserCommand::ClearTagConstraints)
+    }
+
+    // Positions of the collapsed cells whose prototype has the tag
+    #[func]
+    pub fn get_cells_with_tag(&self, tag: GodotString) -> Array<Vector3i> {
+        let tag = tag.to_string();
+        let tagged: HashMap<&str, &Prototype> = self
+            .applied
+            .iter()
+            .filter(|p| p.has_tag(&tag))
+            .map(|p| (p.id.as_str(), p))
+            .collect();
+        Array::from_iter(
+            self.collapsed
+                .iter()
+                .filter(|(_, id)| tagged.contains_key(id.as_str()))
+                .map(|(position, _)| *position),
+        )
+    }
+
+    // Tags of the cell's prototype, or none if it isn't collapsed
+    #[func]
+    pub fn get_cell_tags(&self, position: Vector3i) -> PackedStringArray {
+        let Some(id) = self.collapsed.get(&position) else {
+            return PackedStringArray::new();
+        };
+        self.applied
+            .iter()
+            .find(|p| p.id == *id)
+            .map(|p| {
+                p.tags
+                    .iter()
+                    .map(|tag| GodotString::from(tag.as_str()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    // Weight fields scale the weights of the prototypes picked out by `selector_kind` and `name`
+    //  (0 = prototype id, 1 = mesh name, 2 = tag) depending on where the cell is. Layers stack,
+    //  multiplying where they overlap, and stay in place when the map is regenerated.
+
+    // `values` are multipliers on a grid of `size` stretched over the map, x first, then z, then y.
+    //  Give it a height of 1 to apply the same values at every height.
+    #[func]
+    pub fn add_weight_map(
+        &mut self,
+        selector_kind: i64,
+        name: GodotString,
+        size: Vector3i,
+        values: PackedFloat32Array,
+    ) -> i64 {
+        let source = FieldSource::Grid {
+            size,
+            values: values.to_vec(),
+        };
+        self.add_weight_layer(selector_kind, name, source)
+    }
+
+    // Samples the image's brightness over the map from above, with black mapping to `low` and
+    //  white to `high`
+    #[func]
+    pub fn add_weight_image(
+        &mut self,
+        selector_kind: i64,
+        name: GodotString,
+        image: Gd<Image>,
+        low: f32,
+        high: f32,
+    ) -> i64 {
+        let size = Vector3i::new(image.get_width(), 1, image.get_height());
+        let mut values = vec![];
+        for z in 0..size.z {
+            for x in 0..size.x {
+                let brightness = image.get_pixel(x, z).luminance();
+                values.push(low + (high - low) * brightness);
+            }
+        }
+        self.add_weight_layer(selector_kind, name, FieldSource::Grid { size, values })
+    }
+
+    #[func]
+    pub fn add_weight_texture(
+        &mut self,
+        selector_kind: i64,
+        name: GodotString,
+        texture: Gd<Texture2D>,
+        low: f32,
+        high: f32,
+    ) -> i64 {
+        match texture.get_image() {
+            Some(image) => self.add_weight_image(selector_kind, name, image, low, high),
+            None => {
+                godot_error!("Texture has no image data");
+                -1
+            }
+        }
+    }
+
+    // Smooth noise with features about `scale` cells across, between `low` and `high`
+    #[func]
+    pub fn add_weight_noise(
+        &mut self,
+        selector_kind: i64,
+        name: GodotString,
+        seed: i64,
+        scale: f32,
+        low: f32,
+        high: f32,
+    ) -> i64 {
+        let source = FieldSource::Noise {
+            seed: seed as u64,
+            scale,
+            low,
+            high,
+        };
+        self.add_weight_layer(selector_kind, name, source)
+    }
+
+    #[func]
+    pub fn clear_weight_field(&mut self) -> i64 {
+        self.send_command(CollapserCommand::ClearWeightField)
+    }
+
+    // 0 = linear, 1 = spiral out from the focus, 2 = nearest to the focus first
+    #[func]
+    pub fn set_chunk_order(&mut self, order: i64) -> i64 {
+        let order = match order {
+            0 => ChunkOrder::Linear,
+            1 => ChunkOrder::Spiral,
+            2 => ChunkOrder::NearestToFocus,
+            _ => {
+                godot_error!("Unknown chunk order {}", order);
+                return -1;
+            }
+        };
+        self.send_command(CollapserCommand::SetChunkOrder { order })
+    }
+
+    // How many times a chunk that hits a contradiction is put back and tried again with a new seed,
+    //  and what happens after that: 0 = accept the failure, 1 = split it into smaller chunks,
+    //  2 = grow it by the chunk overlap so more of its neighbours are reset too
+    #[func]
+    pub fn set_retry_policy(&mut self, max_retries: i64, escalation: i64) -> i64 {
+        let escalation = match escalation {
+            0 => Escalation::Accept,
+            1 => Escalation::Shrink,
+            2 => Escalation::WidenReset,
+            _ => {
+                godot_error!("Unknown escalation {}", escalation);
+                return -1;
+            }
+        };
+        let policy = RetryPolicy {
+            max_retries: max_retries.max(0) as u32,
+            escalation,
+        };
+        self.send_command(CollapserCommand::SetRetryPolicy { policy })
+    }
+
+    // Takes a dictionary of cell position (Vector3i) to priority (int). Chunks containing higher
+    //  priority cells are collapsed first; unlisted cells have priority 0.
+    #[func]
+    pub fn set_chunk_priorities(&mut self, priorities: Dictionary) -> i64 {
+        let mut parsed = vec![];
+        for (key, value) in priorities.iter_shared() {
+            match (key.try_to::<Vector3i>(), value.try_to::<i64>()) {
+                (Ok(position), Ok(priority)) => parsed.push((position, priority as i32)),
+                _ => godot_error!("Skipping invalid priority {:?}: {:?}", key, value),
+            }
+        }
+        self.send_command(CollapserCommand::SetChunkOrder {
+            order: ChunkOrder::Custom { priorities: parsed },
+        })
+    }
+
     #[func]
-    pub fn start(&mut self) {
-        self.send_action(CollapserActionType::START)
+    pub fn save_map(&mut self, path: GodotString) -> i64 {
+        self.send_command(CollapserCommand::Save {
+            path: path.to_string(),
+        })
     }
 
+    // Reads `prototype_path` again and rebuilds the map from the result
     #[func]
-    pub fn stop(&mut self) {
-        self.send_action(CollapserActionType::STOP)
+    pub fn reload_prototypes(&mut self) -> i64 {
+        match self.load_prototypes() {
+            Ok(prototypes) => self.send_prototypes(prototypes),
+            Err(message) => {
+                godot_error!("Failed to load prototypes: {}", message);
+                -1
+            }
+        }
+    }
+
+    // The editing functions below change the prototypes as last loaded, including any generated
+    //  rotations and derived adjacency, and keep valid_neighbours symmetric. They return false and
+    //  log an error if the edit wasn't made. The map only picks the changes up on
+    //  apply_prototypes, and get_prototype_data once the map has been rebuilt from them.
+
+    // `data` is a prototype in the JSON schema: "mesh_name", "posX", "weight" and so on
+    #[func]
+    pub fn add_prototype(&mut self, id: GodotString, data: Dictionary) -> bool {
+        let entry = dictionary_to_json(&data);
+        self.edit_prototypes(|protos| {
+            prototype_edit::add_prototype(protos, &id.to_string(), &entry)
+        })
+    }
+
+    // `changes` is any of the JSON schema's fields except "valid_neighbours"
+    #[func]
+    pub fn update_prototype(&mut self, id: GodotString, changes: Dictionary) -> bool {
+        let changes = dictionary_to_json(&changes);
+        self.edit_prototypes(|protos| {
+            prototype_edit::update_prototype(protos, &id.to_string(), &changes)
+        })
+    }
+
+    #[func]
+    pub fn remove_prototype(&mut self, id: GodotString) -> bool {
+        self.edit_prototypes(|protos| prototype_edit::remove_prototype(protos, &id.to_string()))
+    }
+
+    // Lets `b` sit on the `direction` side of `a`, with directions in valid_neighbours order:
+    //  0 = +x, 1 = +y, 2 = -x, 3 = -y, 4 = +z, 5 = -z
+    #[func]
+    pub fn add_adjacency(&mut self, a: GodotString, b: GodotString, direction: i64) -> bool {
+        let direction = usize::try_from(direction).unwrap_or(usize::MAX);
+        self.edit_prototypes(|protos| {
+            prototype_edit::add_adjacency(protos, &a.to_string(), &b.to_string(), direction)
+        })
+    }
+
+    #[func]
+    pub fn remove_adjacency(&mut self, a: GodotString, b: GodotString, direction: i64) -> bool {
+        let direction = usize::try_from(direction).unwrap_or(usize::MAX);
+        self.edit_prototypes(|protos| {
+            prototype_edit::remove_adjacency(protos, &a.to_string(), &b.to_string(), direction)
+        })
+    }
+
+    // Writes JSON, or binary if `path` ends in ".lwfc", or a TOML tileset if it ends in ".toml"
+    #[func]
+    pub fn save_prototypes(&self, path: GodotString) -> bool {
+        match Prototype::save(&self.prototypes, &path.to_string()) {
+            Ok(()) => true,
+            Err(message) => {
+                godot_error!("{}", message);
+                false
+            }
+        }
+    }
+
+    // Reads the prototypes at `prototype_path` the same way the map would, and saves them to
+    //  `destination` in the format its extension picks, as save_prototypes does. Doesn't change
+    //  the prototypes in use.
+    #[func]
+    pub fn convert_prototypes(&self, destination: GodotString) -> bool {
+        let converted = self
+            .read_prototypes()
+            .and_then(|prototypes| Prototype::save(&prototypes, &destination.to_string()));
+        match converted {
+            Ok(()) => true,
+            Err(message) => {
+                godot_error!("{}", message);
+                false
+            }
+        }
+    }
+
+    // Rebuilds the map from the edited prototypes
+    #[func]
+    pub fn apply_prototypes(&mut self) -> i64 {
+        self.send_prototypes(self.prototypes.clone())
+    }
+
+    // Prototype id to a dictionary with "mesh_name", "mesh_rotation" and "tags", for every
+    //  prototype the worker can place. Includes any generated rotations, but not edits that haven't
+    //  been applied yet.
+    #[func]
+    pub fn get_prototype_data(&self) -> Dictionary {
+        prototype_data(&self.applied)
+    }
+
+    // As get_prototype_data, but for the prototypes as edited, which apply_prototypes would send
+    #[func]
+    pub fn get_edited_prototype_data(&self) -> Dictionary {
+        prototype_data(&self.prototypes)
+    }
+
+    // Checks the prototypes at `prototype_path`, with rotations and adjacency handled the same
+    //  way as for the map. Returns a dictionary per issue with "severity" ("warning" or "error"),
+    //  "prototype" and "message". Used by src/validate_tileset.gd.
+    #[func]
+    pub fn validate_tileset(&self) -> Array<Dictionary> {
+        let issues = match self.read_prototypes() {
+            Ok(prototypes) => tileset_validation::validate(&prototypes),
+            Err(message) => vec![TilesetIssue {
+                severity: Severity::Error,
+                prototype: "".into(),
+                message,
+            }],
+        };
+        Array::from_iter(issues.iter().map(|issue| {
+            let mut dict = Dictionary::new();
+            dict.set("severity", GodotString::from(issue.severity.name()));
+            dict.set("prototype", GodotString::from(issue.prototype.clone()));
+            dict.set("message", GodotString::from(issue.message.clone()));
+            dict
+        }))
+    }
+
+    // Checks whether the prototypes at `prototype_path` can fill an unbounded map, along the
+    //  vertical axis too if `vertical` is set. Returns a dictionary with:
+    //  - "eliminated": a dictionary per prototype that always leads to a contradiction away from
+    //    the map's edges, with "prototype" and the "side" that has nothing left to fit against
+    //  - "period" and "periodic_tile": the smallest block found that tiles forever, as a
+    //    dictionary of position to prototype id. Empty if there isn't one.
+    //  - "search_complete": false if the search gave up before it could rule out every period
+    #[func]
+    pub fn analyze_tileset(&self, vertical: bool) -> Dictionary {
+        let mut result = Dictionary::new();
+        let prototypes = match self.read_prototypes() {
+            Ok(prototypes) => prototypes,
+            Err(message) => {
+                godot_error!("Failed to load prototypes: {}", message);
+                return result;
+            }
+        };
+
+        let analysis = SolvabilityAnalysis {
+            vertical,
+            ..Default::default()
+        };
+        let report = analysis.run(&prototypes);
+
+        let eliminated = Array::from_iter(report.eliminated.iter().map(|eliminated| {
+            let mut dict = Dictionary::new();
+            dict.set("prototype", GodotString::from(eliminated.id.clone()));
+            dict.set("side", GodotString::from(eliminated.side));
+            dict
+        }));
+        let mut tile = Dictionary::new();
+        let mut period = Vector3i::ZERO;
+        if let Some(periodic_tile) = report.periodic_tile {
+            period = periodic_tile.period;
+            for (position, id) in periodic_tile.cells {
+                tile.set(position, GodotString::from(id));
+            }
+        }
+
+        result.set("eliminated", eliminated);
+        result.set("period", period);
+        result.set("periodic_tile", tile);
+        result.set("search_complete", report.search_complete);
+        result
+    }
+
+    #[func]
+    pub fn load_map(&mut self, path: GodotString) -> i64 {
+        self.send_reset_command(CollapserCommand::Load {
+            path: path.to_string(),
+            as_fill: false,
+        })
+    }
+
+    // Loads a finished map saved with save_map, and modifies it in blocks from there. Fails if any
+    //  of its cells aren't collapsed, or don't fit their neighbours.
+    #[func]
+    pub fn load_map_as_fill(&mut self, path: GodotString) -> i64 {
+        self.send_reset_command(CollapserCommand::Load {
+            path: path.to_string(),
+            as_fill: true,
+        })
     }
 
     pub fn tick(&mut self, _delta: f64) -> Option<()> {
         let update = self.receive_update()?;
+        if let Some(message) = update.error {
+            godot_error!("Worker thread failed: {}", message);
+            self.node.emit_signal(
+                "worker_failed".into(),
+                &[GodotString::from(message).to_variant()],
+            );
+            return None;
+        }
+
         if let Some(new_state) = update.new_state {
-            godot_print!("Ignoring state update from thread: {:?}", new_state);
+            match new_state {
+                CollapserState::COMPLETED => {
+                    self.node.emit_signal("map_completed".into(), &[]);
+                }
+                _ => godot_print!("Ignoring state update from thread: {:?}", new_state),
+            }
+        }
+
+        if let Some(reset) = update.reset {
+            self.pending_resets.retain(|id| *id != reset.request_id);
+            if let Some(index) = self
+                .applying
+                .iter()
+                .position(|(id, _)| *id == reset.request_id)
+            {
+                self.applied = self.applying.remove(index).1;
+            }
+            let config = reset.config;
+            self.map_origin = config.origin;
+            self.map_size = config.size;
+            self.chunk_size = config.chunk_size;
+            self.chunk_overlap = config.chunk_overlap;
+            self.block_storage = config.storage == StorageKind::Blocks;
+            self.modify_in_blocks = config.solver == SolverMode::ModifyInBlocks;
+            self.fill_prototype = config.fill.clone().unwrap_or_default().into();
+            self.collapsed.clear();
+            self.node.emit_signal(
+                "map_reset".into(),
+                &[config.origin.to_variant(), config.size.to_variant()],
+            );
+        }
+
+        if let Some(result) = update.command_result {
+            // A failed reset never sends its MapReset, so stop waiting for it here
+            self.pending_resets.retain(|id| *id != result.request_id);
+            self.applying.retain(|(id, _)| *id != result.request_id);
+            let error = GodotString::from(result.error.unwrap_or_default());
+            self.node.emit_signal(
+                "command_completed".into(),
+                &[(result.request_id as i64).to_variant(), error.to_variant()],
+            );
+        }
+
+        // Like changes, chunk events from before a pending reset belong to the old map
+        if !self.pending_resets.is_empty() {
+            return None;
+        }
+
+        for event in update.chunk_events.iter() {
+            self.emit_chunk_event(event);
         }
 
         let changes = update.changes?;
+        for change in changes.iter() {
+            if change.new_protos.is_empty() || change.new_protos.contains(',') {
+                self.collapsed.remove(&change.position);
+            } else {
+                self.collapsed
+                    .insert(change.position, change.new_protos.clone());
+            }
+        }
+
         // godot_print!("Cells changed: {:?}", changes.len());
         let changes_array = Array::from_iter(changes.iter().map(|c| c.to_godot()));
         self.node
@@ -112,6 +855,191 @@ impl LWFCDriver {
         Some(())
     }
 
+    fn emit_chunk_event(&mut self, event: &ChunkEvent) {
+        let bounds = [event.position.to_variant(), event.size.to_variant()];
+        match event.kind {
+            ChunkEventKind::Started => {
+                self.node.emit_signal("chunk_started".into(), &bounds);
+            }
+            ChunkEventKind::SeamPropagated => {
+                self.node
+                    .emit_signal("chunk_seams_propagated".into(), &bounds);
+            }
+            ChunkEventKind::Completed | ChunkEventKind::Failed => {
+                for violation in event.violations.iter() {
+                    self.node.emit_signal(
+                        "seam_violation".into(),
+                        &[
+                            violation.position.to_variant(),
+                            violation.neighbor.to_variant(),
+                            violation.repairing.to_variant(),
+                        ],
+                    );
+                }
+
+                let [position, size] = bounds;
+                self.node.emit_signal(
+                    "chunk_completed".into(),
+                    &[position, size, chunk_stats(event).to_variant()],
+                );
+            }
+            ChunkEventKind::Retried => {
+                let [position, size] = bounds;
+                self.node.emit_signal(
+                    "chunk_retried".into(),
+                    &[position, size, chunk_stats(event).to_variant()],
+                );
+            }
+        }
+    }
+
+    // Ask the worker to stop, then wait a bounded amount of time for it to exit.
+    // A worker that doesn't respond in time is detached rather than blocking the main thread.
+    fn shutdown(&mut self) {
+        let Some(handle) = self.handle.take() else {
+            return;
+        };
+
+        if !handle.is_finished() {
+            self.stop();
+        }
+        // Hanging up also stops the worker, even if the STOP didn't fit in the channel
+        self.send_to_thread = None;
+        self.unsent.clear();
+
+        let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+        while !handle.is_finished() {
+            // Keep draining so the worker is never stuck waiting for room to send
+            if let Some(receiver) = &self.recv_in_main {
+                while receiver.try_recv().is_ok() {}
+            }
+
+            if Instant::now() >= deadline {
+                godot_error!(
+                    "Worker thread didn't stop within {:?}, detaching it.",
+                    SHUTDOWN_TIMEOUT
+                );
+                return;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        if handle.join().is_err() {
+            godot_error!("Worker thread panicked while shutting down.");
+        }
+    }
+
+    // These exports can be changed at any time from the editor or GDScript, so forward them when
+    //  they do
+    fn sync_exports(&mut self) {
+        // If the command couldn't be sent, the sent value stays as it was and it's tried next frame
+        if self.collapses_per_second != self.sent_collapses_per_second {
+            let request_id = self.send_command(CollapserCommand::SetSpeed {
+                collapses_per_second: self.collapses_per_second,
+            });
+            if request_id >= 0 {
+                self.sent_collapses_per_second = self.collapses_per_second;
+            }
+        }
+
+        // Only the latest focus matters, so it replaces any the worker hasn't picked up yet
+        if self.focus != self.sent_focus {
+            self.sent_focus = self.focus;
+            if let Ok(mut slot) = self.focus_slot.lock() {
+                *slot = Some(self.focus);
+            }
+        }
+    }
+
+    fn map_config(
+        &self,
+        origin: Vector3i,
+        size: Vector3i,
+        chunk_size: Vector3i,
+        chunk_overlap: Vector3i,
+    ) -> MapConfig {
+        MapConfig {
+            origin,
+            size,
+            chunk_size,
+            chunk_overlap,
+            storage: if self.block_storage {
+                StorageKind::Blocks
+            } else {
+                StorageKind::Dense
+            },
+            solver: if self.modify_in_blocks {
+                SolverMode::ModifyInBlocks
+            } else {
+                SolverMode::Chunked
+            },
+            fill: Some(self.fill_prototype.to_string()).filter(|id| !id.is_empty()),
+        }
+    }
+
+    fn read_prototypes(&self) -> Result<Vec<Prototype>, String> {
+        let path = self.prototype_path.to_string();
+        let mut prototypes = Prototype::load(&path)?;
+        if PrototypeFormat::from_path(&path).is_authored() {
+            return Ok(prototypes);
+        }
+        if self.expand_rotations {
+            prototypes = RotationExpansion::default().apply(&prototypes);
+        }
+        if self.derive_adjacency {
+            SocketRules::default().apply(&mut prototypes);
+        }
+        Ok(prototypes)
+    }
+
+    fn load_prototypes(&mut self) -> Result<Vec<Prototype>, String> {
+        let prototypes = self.read_prototypes()?;
+        godot_print!("Loaded {} prototypes", prototypes.len());
+        // Problems with the tileset are reported, but don't stop it loading
+        for issue in tileset_validation::validate(&prototypes) {
+            match issue.severity {
+                Severity::Warning => godot_warn!("{}", issue),
+                Severity::Error => godot_error!("{}", issue),
+            }
+        }
+        self.prototypes = prototypes.clone();
+        Ok(prototypes)
+    }
+
+    fn add_weight_layer(
+        &mut self,
+        selector_kind: i64,
+        name: GodotString,
+        source: FieldSource,
+    ) -> i64 {
+        let name = name.to_string();
+        let selector = match selector_kind {
+            0 => PrototypeSelector::Id(name),
+            1 => PrototypeSelector::Mesh(name),
+            2 => PrototypeSelector::Tag(name),
+            _ => {
+                godot_error!("Unknown selector kind {}", selector_kind);
+                return -1;
+            }
+        };
+        self.send_command(CollapserCommand::AddWeightLayer {
+            layer: WeightLayer { selector, source },
+        })
+    }
+
+    fn edit_prototypes(
+        &mut self,
+        edit: impl FnOnce(&mut Vec<Prototype>) -> Result<(), String>,
+    ) -> bool {
+        match edit(&mut self.prototypes) {
+            Ok(()) => true,
+            Err(message) => {
+                godot_error!("Failed to edit prototypes: {}", message);
+                false
+            }
+        }
+    }
+
     fn receive_update(&mut self) -> Option<DriverUpdate> {
         match &self.recv_in_main {
             Some(receiver) => match receiver.try_recv() {
@@ -125,13 +1053,180 @@ impl LWFCDriver {
         }
     }
 
-    fn send_action(&mut self, action_type: CollapserActionType) {
-        match &self.send_to_thread {
-            Some(sender) => match sender.send(CollapserAction::new(action_type)) {
-                Ok(_) => (),
-                Err(e) => godot_error!("Failed to send action! {}", e),
-            },
-            None => godot_error!("Tried to send action, but there's no sender!"),
+    fn send_reset_command(&mut self, command: CollapserCommand) -> i64 {
+        let request_id = self.send_command(command);
+        if request_id >= 0 {
+            self.pending_resets.push(request_id as u64);
+        }
+        request_id
+    }
+
+    fn send_prototypes(&mut self, prototypes: Vec<Prototype>) -> i64 {
+        let request_id = self.send_reset_command(CollapserCommand::SetPrototypes {
+            prototypes: prototypes.clone(),
+        });
+        if request_id >= 0 {
+            self.applying.push((request_id as u64, prototypes));
         }
+        request_id
     }
+
+    fn send_command(&mut self, command: CollapserCommand) -> i64 {
+        if self.send_to_thread.is_none() {
+            godot_error!("Tried to send action, but there's no sender!");
+            return -1;
+        }
+
+        let request_id = self.next_request_id;
+        self.next_request_id += 1;
+        self.unsent
+            .push_back(CollapserAction::new(request_id, command));
+
+        if let Err(message) = self.send_unsent() {
+            godot_error!("Failed to send action! {}", message);
+            return -1;
+        }
+        if let Some(action) = self.unsent.back().filter(|a| a.request_id == request_id) {
+            godot_warn!(
+                "Command queue is full, holding {} (request {}) back until there's room",
+                action.command.name(),
+                action.request_id
+            );
+        }
+        request_id as i64
+    }
+
+    // Sends as many held back commands as the channel has room for. If the worker has hung up,
+    //  they're all dropped.
+    fn send_unsent(&mut self) -> Result<(), String> {
+        let Some(sender) = &self.send_to_thread else {
+            return Ok(());
+        };
+        while let Some(action) = self.unsent.pop_front() {
+            match sender.try_send(action) {
+                Ok(()) => {}
+                Err(TrySendError::Full(action)) => {
+                    self.unsent.push_front(action);
+                    break;
+                }
+                Err(TrySendError::Disconnected(action)) => {
+                    let more = self.unsent.len();
+                    self.unsent.clear();
+                    return Err(format!(
+                        "The worker has stopped, dropping {} (request {}) and {} more",
+                        action.command.name(),
+                        action.request_id,
+                        more
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Takes a dictionary of name to weight. Invalid entries are skipped.
+fn parse_weights(
+    weights: Dictionary,
+    selector: fn(String) -> PrototypeSelector,
+) -> Vec<(PrototypeSelector, f32)> {
+    let mut parsed = vec![];
+    for (key, value) in weights.iter_shared() {
+        let weight = value
+            .try_to::<f64>()
+            .or_else(|_| value.try_to::<i64>().map(|w| w as f64));
+        match (key.try_to::<GodotString>(), weight) {
+            (Ok(name), Ok(weight)) => parsed.push((selector(name.to_string()), weight as f32)),
+            _ => godot_error!("Skipping invalid weight {:?}: {:?}", key, value),
+        }
+    }
+    parsed
+}
+
+fn parse_selectors(
+    names: PackedStringArray,
+    selector: fn(String) -> PrototypeSelector,
+) -> Vec<PrototypeSelector> {
+    names
+        .to_vec()
+        .into_iter()
+        .map(|name| selector(name.to_string()))
+        .collect()
+}
+
+// Converts the plain values GDScript passes around (strings, numbers, bools, arrays and
+//  dictionaries) to JSON. Anything else becomes null.
+fn variant_to_json(value: &Variant) -> Value {
+    if let Ok(string) = value.try_to::<GodotString>() {
+        return Value::from(string.to_string());
+    }
+    if let Ok(int) = value.try_to::<i64>() {
+        return Value::from(int);
+    }
+    if let Ok(float) = value.try_to::<f64>() {
+        return Value::from(float);
+    }
+    if let Ok(boolean) = value.try_to::<bool>() {
+        return Value::from(boolean);
+    }
+    if let Ok(strings) = value.try_to::<PackedStringArray>() {
+        let strings = strings
+            .to_vec()
+            .iter()
+            .map(|s| Value::from(s.to_string()))
+            .collect();
+        return Value::Array(strings);
+    }
+    if let Ok(array) = value.try_to::<VariantArray>() {
+        return Value::Array(array.iter_shared().map(|v| variant_to_json(&v)).collect());
+    }
+    if let Ok(dictionary) = value.try_to::<Dictionary>() {
+        return dictionary_to_json(&dictionary);
+    }
+    Value::Null
+}
+
+fn dictionary_to_json(dictionary: &Dictionary) -> Value {
+    let mut object = Map::new();
+    for (key, value) in dictionary.iter_shared() {
+        let key = match key.try_to::<GodotString>() {
+            Ok(key) => key.to_string(),
+            Err(_) => key.stringify().to_string(),
+        };
+        object.insert(key, variant_to_json(&value));
+    }
+    Value::Object(object)
+}
+
+fn parse_tags(tags: PackedStringArray) -> Vec<String> {
+    tags.to_vec().iter().map(|tag| tag.to_string()).collect()
+}
+
+fn prototype_data(prototypes: &[Prototype]) -> Dictionary {
+    let mut data = Dictionary::new();
+    for proto in prototypes.iter() {
+        let mut datum = Dictionary::new();
+        datum.set("mesh_name", GodotString::from(proto.mesh_name.clone()));
+        datum.set("mesh_rotation", proto.mesh_rotation as i64);
+        let tags: PackedStringArray = proto
+            .tags
+            .iter()
+            .map(|tag| GodotString::from(tag.as_str()))
+            .collect();
+        datum.set("tags", tags);
+        data.set(GodotString::from(proto.id.clone()), datum);
+    }
+    data
+}
+
+fn chunk_stats(event: &ChunkEvent) -> Dictionary {
+    let mut stats = Dictionary::new();
+    stats.set("failed", event.kind == ChunkEventKind::Failed);
+    stats.set("collapses", event.stats.collapses as i64);
+    stats.set("cells_changed", event.stats.cells_changed as i64);
+    stats.set("contradictions", event.stats.contradictions as i64);
+    stats.set("seam_violations", event.stats.seam_violations as i64);
+    stats.set("attempts", event.stats.attempts as i64);
+    stats.set("elapsed_ms", event.stats.elapsed_ms as i64);
+    stats
 }
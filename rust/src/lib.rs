@@ -1,5 +1,8 @@
 use godot::prelude::*;
 
+#[macro_use]
+mod log;
+
 mod driver;
 mod models;
 mod worker;
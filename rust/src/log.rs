@@ -0,0 +1,20 @@
+// Logging for code that also runs in unit tests. Godot's print macros need the engine, which
+//  isn't there when testing, so tests write to stderr instead.
+
+macro_rules! log_print {
+    ($($args:tt)*) => {{
+        #[cfg(not(test))]
+        godot::prelude::godot_print!($($args)*);
+        #[cfg(test)]
+        eprintln!($($args)*);
+    }};
+}
+
+macro_rules! log_error {
+    ($($args:tt)*) => {{
+        #[cfg(not(test))]
+        godot::prelude::godot_error!($($args)*);
+        #[cfg(test)]
+        eprintln!($($args)*);
+    }};
+}
@@ -0,0 +1,51 @@
+use godot::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkEventKind {
+    // The chunk was picked as the next one to collapse
+    Started,
+    // Overlapping cells were reset and constraints from finished neighbours were pulled in
+    SeamPropagated,
+    // Every cell in the chunk was collapsed without contradictions, and its seams with finished
+    //  chunks all line up
+    Completed,
+    // The chunk ran out of cells to collapse, but hit at least one contradiction on the way or left
+    //  mismatched cells along a seam
+    Failed,
+    // The chunk failed and was put back as it was, to be tried again or replaced by the retry
+    //  policy's escalation
+    Retried,
+}
+
+// Running totals for the chunk currently being collapsed
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChunkStats {
+    pub collapses: u32,
+    pub cells_changed: u32,
+    pub contradictions: u32,
+    pub seam_violations: u32,
+    // Including this one
+    pub attempts: u32,
+    pub elapsed_ms: u64,
+}
+
+// A pair of adjacent cells, one either side of a chunk boundary, that can't sit next to each other
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SeamViolation {
+    // The cell inside the chunk that was just finished
+    pub position: Vector3i,
+    // Its neighbour in an earlier chunk
+    pub neighbor: Vector3i,
+    // False once the cell has been repaired too many times, in which case it's left as it is
+    pub repairing: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChunkEvent {
+    pub kind: ChunkEventKind,
+    pub position: Vector3i,
+    pub size: Vector3i,
+    pub stats: ChunkStats,
+    // Only set on Completed and Failed events
+    pub violations: Vec<SeamViolation>,
+}
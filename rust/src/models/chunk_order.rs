@@ -0,0 +1,15 @@
+use godot::prelude::*;
+
+// Strategy used to pick which chunk to collapse next
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChunkOrder {
+    // The order the chunks were generated in
+    Linear,
+    // Ring by ring outward from the focus
+    Spiral,
+    // Whichever remaining chunk is closest to the focus when the previous chunk finishes
+    NearestToFocus,
+    // Each chunk takes the highest priority of any listed cell it contains (default 0), highest
+    //  first
+    Custom { priorities: Vec<(Vector3i, i32)> },
+}
@@ -1,25 +1,122 @@
 use godot::prelude::*;
 
-#[repr(i16)]
-#[derive(Property, Debug)]
-pub enum CollapserActionType {
-    NOOP = 0,
-    START = 1,
-    PAUSE = 2,
-    STOP = 3,
+use super::{
+    chunk_order::ChunkOrder, map_config::MapConfig, prototype::Prototype,
+    prototype_selector::PrototypeSelector, retry_policy::RetryPolicy,
+    tag_constraint::TagConstraint, weight_field::WeightLayer,
+};
+
+#[derive(Debug, Clone)]
+pub enum CollapserCommand {
+    Start,
+    Pause,
+    Stop,
+    // Run exactly `count` iterations, then pause
+    Step {
+        count: u32,
+    },
+    // Rate limit for the worker loop. Zero means as fast as possible.
+    SetSpeed {
+        collapses_per_second: f64,
+    },
+    // Rebuild the map in place with a new seed
+    Reset {
+        seed: u64,
+    },
+    // Rebuild the map with a new shape, origin or storage backend
+    Resize {
+        config: MapConfig,
+    },
+    // Collapse each cell to the given prototype id and keep it there across chunk resets
+    PinCells {
+        cells: Vec<(Vector3i, String)>,
+    },
+    RegenerateRegion {
+        position: Vector3i,
+        size: Vector3i,
+    },
+    // Later entries win where selectors overlap. Cells keep their new weights until the map is
+    //  replaced.
+    SetWeights {
+        weights: Vec<(PrototypeSelector, f32)>,
+    },
+    // Disabled prototypes are removed from every cell that isn't collapsed yet, and aren't offered
+    //  to cells that are reset. Pinned cells are left alone.
+    SetEnabled {
+        selectors: Vec<PrototypeSelector>,
+        enabled: bool,
+    },
+    // Like SetEnabled, but only within the constraint's region
+    AddTagConstraint {
+        constraint: TagConstraint,
+    },
+    ClearTagConstraints,
+    // Scale the weights of the selected prototypes by position, on top of any earlier layers
+    AddWeightLayer {
+        layer: WeightLayer,
+    },
+    ClearWeightField,
+    // Applies from the next chunk onwards
+    SetChunkOrder {
+        order: ChunkOrder,
+    },
+    // Applies to chunks that fail from now on
+    SetRetryPolicy {
+        policy: RetryPolicy,
+    },
+    // Rebuild the map in place from a different prototype set
+    SetPrototypes {
+        prototypes: Vec<Prototype>,
+    },
+    Save {
+        path: String,
+    },
+    // With `as_fill`, the saved map has to be finished, and is modified in blocks from there
+    Load {
+        path: String,
+        as_fill: bool,
+    },
+}
+
+impl CollapserCommand {
+    // For logging, since some commands carry a whole prototype set or weight grid
+    pub fn name(&self) -> &'static str {
+        match self {
+            CollapserCommand::Start => "Start",
+            CollapserCommand::Pause => "Pause",
+            CollapserCommand::Stop => "Stop",
+            CollapserCommand::Step { .. } => "Step",
+            CollapserCommand::SetSpeed { .. } => "SetSpeed",
+            CollapserCommand::Reset { .. } => "Reset",
+            CollapserCommand::Resize { .. } => "Resize",
+            CollapserCommand::PinCells { .. } => "PinCells",
+            CollapserCommand::RegenerateRegion { .. } => "RegenerateRegion",
+            CollapserCommand::SetWeights { .. } => "SetWeights",
+            CollapserCommand::SetEnabled { .. } => "SetEnabled",
+            CollapserCommand::AddTagConstraint { .. } => "AddTagConstraint",
+            CollapserCommand::ClearTagConstraints => "ClearTagConstraints",
+            CollapserCommand::AddWeightLayer { .. } => "AddWeightLayer",
+            CollapserCommand::ClearWeightField => "ClearWeightField",
+            CollapserCommand::SetChunkOrder { .. } => "SetChunkOrder",
+            CollapserCommand::SetRetryPolicy { .. } => "SetRetryPolicy",
+            CollapserCommand::SetPrototypes { .. } => "SetPrototypes",
+            CollapserCommand::Save { .. } => "Save",
+            CollapserCommand::Load { .. } => "Load",
+        }
+    }
 }
 
 #[derive(GodotClass, Debug)]
 pub struct CollapserAction {
-    pub action_type: CollapserActionType,
-    pub payload: Option<String>,
+    pub request_id: u64,
+    pub command: CollapserCommand,
 }
 
 impl CollapserAction {
-    pub fn new(action_type: CollapserActionType) -> Self {
+    pub fn new(request_id: u64, command: CollapserCommand) -> Self {
         Self {
-            action_type,
-            payload: None,
+            request_id,
+            command,
         }
     }
 }
@@ -6,4 +6,5 @@ pub enum CollapserState {
     IDLE = 1,
     PROCESSING = 2,
     STOPPED = 3,
+    COMPLETED = 4,
 }
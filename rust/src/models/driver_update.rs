@@ -1,6 +1,11 @@
+use std::collections::HashSet;
+
 use godot::prelude::*;
 
-use super::{collapser_state::CollapserState, prototype::Prototype};
+use super::{
+    chunk_event::ChunkEvent, collapser_state::CollapserState, map_config::MapConfig,
+    prototype::Prototype,
+};
 
 #[derive(Debug, Clone)]
 pub struct CellChange {
@@ -25,15 +30,64 @@ impl CellChangeGodot {
     }
 }
 
+// Acknowledgement for a single CollapserAction, matched up by request id.
+// `error` is None if the command was applied successfully.
+#[derive(Debug)]
+pub struct CommandResult {
+    pub request_id: u64,
+    pub error: Option<String>,
+}
+
+// Sent when the worker replaces its map. Everything Godot knows about the old map is stale.
+#[derive(Debug, Clone)]
+pub struct MapReset {
+    pub request_id: u64,
+    pub config: MapConfig,
+}
+
 #[derive(GodotClass, Debug)]
 pub struct DriverUpdate {
     pub new_state: Option<CollapserState>,
     pub changes: Option<Vec<CellChangeGodot>>,
+    pub command_result: Option<CommandResult>,
+    pub reset: Option<MapReset>,
+    // In the order they happened
+    pub chunk_events: Vec<ChunkEvent>,
+    // Set when the worker failed to start or panicked. No further updates will follow.
+    pub error: Option<String>,
 }
 
 impl DriverUpdate {
     pub fn new(new_state: Option<CollapserState>, changes: Option<Vec<CellChangeGodot>>) -> Self {
-        Self { new_state, changes }
+        Self {
+            new_state,
+            changes,
+            command_result: None,
+            reset: None,
+            chunk_events: vec![],
+            error: None,
+        }
+    }
+
+    pub fn new_error(message: String) -> Self {
+        let mut update = DriverUpdate::new(None, None);
+        update.error = Some(message);
+        update
+    }
+
+    pub fn new_reset(reset: MapReset) -> Self {
+        let mut update = DriverUpdate::new(None, None);
+        update.reset = Some(reset);
+        update
+    }
+
+    pub fn new_command_result(request_id: u64, result: Result<(), String>) -> Self {
+        let mut update = DriverUpdate::new(None, None);
+        update.command_result = Some(CommandResult {
+            request_id,
+            error: result.err(),
+        });
+        update
     }
 
     pub fn new_state(new_state: CollapserState) -> Self {
@@ -51,4 +105,25 @@ impl DriverUpdate {
             ),
         )
     }
+
+    pub fn is_changes_only(&self) -> bool {
+        self.changes.is_some()
+            && self.new_state.is_none()
+            && self.command_result.is_none()
+            && self.reset.is_none()
+            && self.chunk_events.is_empty()
+            && self.error.is_none()
+    }
+
+    // Fold a later batch of changes into this one. Only the latest change for each cell is kept.
+    pub fn merge_changes(&mut self, later: DriverUpdate) {
+        let Some(mut later_changes) = later.changes else {
+            return;
+        };
+
+        let changes = self.changes.get_or_insert_with(Vec::new);
+        let updated: HashSet<Vector3i> = later_changes.iter().map(|c| c.position).collect();
+        changes.retain(|c| !updated.contains(&c.position));
+        changes.append(&mut later_changes);
+    }
 }
@@ -0,0 +1,38 @@
+use godot::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// How a map stores its cells
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageKind {
+    // One flat array over the map bounds, with precomputed neighbours
+    Dense,
+    // Fixed-size blocks keyed by block coordinate. Maps saved before it was renamed say "Sparse".
+    #[serde(alias = "Sparse")]
+    Blocks,
+}
+
+// How a map gets from nothing to a finished world
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SolverMode {
+    // Chunks start out undecided and are collapsed one after another, resetting where they overlap
+    //  earlier chunks
+    #[default]
+    Chunked,
+    // Every cell starts out as a valid fill, and blocks of it are re-solved one at a time. A block
+    //  that can't be solved is put back as it was, so the map is valid after every block.
+    ModifyInBlocks,
+}
+
+// Shape of a map. `origin` is the position of the map's lowest corner and may be negative.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapConfig {
+    pub origin: Vector3i,
+    pub size: Vector3i,
+    pub chunk_size: Vector3i,
+    pub chunk_overlap: Vector3i,
+    pub storage: StorageKind,
+    pub solver: SolverMode,
+    // The prototype every cell starts out as when modifying in blocks. None for maps that start
+    //  from a finished map instead.
+    pub fill: Option<String>,
+}
@@ -0,0 +1,40 @@
+use std::fs;
+
+use serde::{Deserialize, Serialize};
+
+use super::map_config::{SolverMode, StorageKind};
+
+// On-disk representation of a map in progress. Cells are stored in [y][x][z] order from the origin,
+// flattened, with each cell's remaining possibilities listed by prototype id.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct MapSnapshot {
+    pub origin: [i32; 3],
+    pub size: [i32; 3],
+    pub chunk_size: [i32; 3],
+    pub chunk_overlap: [i32; 3],
+    pub storage: StorageKind,
+    pub solver: SolverMode,
+    pub fill: Option<String>,
+    // Position and size of every chunk, including regenerated regions
+    pub chunks: Vec<([i32; 3], [i32; 3])>,
+    // Indices into `chunks`
+    pub processed_chunks: Vec<usize>,
+    pub current_chunk: Option<usize>,
+    pub requested_chunks: Vec<usize>,
+    pub cells: Vec<Vec<String>>,
+    pub pinned: Vec<([i32; 3], String)>,
+}
+
+impl MapSnapshot {
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let contents =
+            serde_json::to_string(self).map_err(|e| format!("Failed to serialize map: {}", e))?;
+        fs::write(path, contents).map_err(|e| format!("Failed to write '{}': {}", path, e))
+    }
+
+    pub fn load(path: &str) -> Result<Self, String> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| format!("Failed to read '{}': {}", path, e))?;
+        serde_json::from_str(&contents).map_err(|e| format!("Failed to parse '{}': {}", path, e))
+    }
+}
@@ -1,4 +1,30 @@
+pub(crate) mod chunk_event;
+pub(crate) mod chunk_order;
 pub(crate) mod collapser_action;
 pub(crate) mod collapser_state;
 pub(crate) mod driver_update;
+pub(crate) mod map_config;
+pub(crate) mod map_snapshot;
 pub(crate) mod prototype;
+pub(crate) mod prototype_binary;
+pub(crate) mod prototype_edit;
+pub(crate) mod prototype_selector;
+pub(crate) mod prototype_toml;
+pub(crate) mod retry_policy;
+pub(crate) mod tag_constraint;
+pub(crate) mod tileset_analysis;
+pub(crate) mod tileset_validation;
+pub(crate) mod weight_field;
+
+mod prototype_binary_test;
+mod prototype_edit_test;
+mod prototype_selector_test;
+mod prototype_test;
+mod prototype_toml_test;
+mod tag_constraint_test;
+mod tileset_analysis_test;
+mod tileset_validation_test;
+mod weight_field_test;
+
+#[cfg(test)]
+pub(crate) mod test_fixtures;
@@ -1,15 +1,37 @@
-use std::{fs::File, io::Read};
+use std::{cmp::Ordering, collections::HashMap, fs};
 
-use godot::{builtin::Vector3i, log::godot_print};
-use serde::Deserialize;
+use godot::{
+    builtin::{PackedByteArray, Vector3i},
+    engine::{file_access::ModeFlags, FileAccess},
+};
+use serde::{Deserialize, Serialize, Serializer};
 use serde_json::{json, Value};
 
-const P_X: usize = 0;
-const P_Y: usize = 1;
-const N_X: usize = 2;
-const N_Y: usize = 3;
-const P_Z: usize = 4;
-const N_Z: usize = 5;
+use super::{prototype_binary, prototype_toml};
+
+pub(crate) const P_X: usize = 0;
+pub(crate) const P_Y: usize = 1;
+pub(crate) const N_X: usize = 2;
+pub(crate) const N_Y: usize = 3;
+pub(crate) const P_Z: usize = 4;
+pub(crate) const N_Z: usize = 5;
+
+// Names of the valid_neighbors directions, in order
+pub(crate) const DIRECTION_NAMES: [&str; 6] = ["+x", "+y", "-x", "-y", "+z", "-z"];
+// The index of the opposite of each valid_neighbors direction
+pub(crate) const OPPOSITE: [usize; 6] = [N_X, N_Y, P_X, P_Y, N_Z, P_Z];
+// The cell offset each valid_neighbors direction points along, matching compatible_with
+pub(crate) const DIRECTION_OFFSETS: [Vector3i; 6] = [
+    Vector3i::RIGHT,
+    Vector3i::FORWARD,
+    Vector3i::LEFT,
+    Vector3i::BACK,
+    Vector3i::UP,
+    Vector3i::DOWN,
+];
+
+// The four sides around the vertical axis, in the order a quarter turn moves them
+pub(crate) const HORIZONTAL: [usize; 4] = [P_X, P_Y, N_X, N_Y];
 
 #[derive(PartialEq, Clone, Debug, Deserialize)]
 pub struct Prototype {
@@ -27,11 +49,13 @@ pub struct Prototype {
     pub weight: f32,
     pub no_id: i32,
     pub no_id_sym: i32,
+    // Categories like "walkable" or "water", for rules that apply to many prototypes at once
+    pub tags: Vec<String>,
     pub valid_neighbors: Vec<Vec<String>>,
 }
 
 impl Prototype {
-    fn from_json_value(id: String, json: &Value) -> Option<Self> {
+    pub(crate) fn from_json_value(id: String, json: &Value) -> Option<Self> {
         match json {
             Value::Object(obj) => {
                 let mesh_name = obj.get("mesh_name")?.as_str()?.to_string();
@@ -47,8 +71,21 @@ impl Prototype {
                 let weight = obj.get("weight")?.as_f64()? as f32;
                 let no_id = obj.get("no_id").or(Some(&json!(0)))?.as_i64()? as i32;
                 let no_id_sym = obj.get("no_id_sym").or(Some(&json!(0)))?.as_i64()? as i32;
+                let tags = match obj.get("tags") {
+                    Some(value) => value
+                        .as_array()?
+                        .iter()
+                        .map(|tag| tag.as_str().map(String::from))
+                        .collect::<Option<Vec<String>>>()?,
+                    None => vec![],
+                };
 
-                let neighbors_json = obj.get("valid_neighbours")?.as_array()?;
+                // Optional, for tilesets whose adjacency is derived from their sockets instead
+                let empty = vec![];
+                let neighbors_json = match obj.get("valid_neighbours") {
+                    Some(value) => value.as_array()?,
+                    None => &empty,
+                };
 
                 let mut valid_neighbors = vec![];
                 for neighbor_value in neighbors_json {
@@ -59,7 +96,7 @@ impl Prototype {
                                 Some(neighbor_string) => {
                                     valid_neighbor_row.push(neighbor_string.to_string())
                                 }
-                                None => godot_print!("skipping neighbor as it's not a string!"),
+                                None => log_print!("skipping neighbor as it's not a string!"),
                             }
                         }
                         valid_neighbors.push(valid_neighbor_row);
@@ -82,6 +119,7 @@ impl Prototype {
                     weight,
                     no_id,
                     no_id_sym,
+                    tags,
                     valid_neighbors,
                 })
             }
@@ -89,29 +127,76 @@ impl Prototype {
         }
     }
 
-    pub fn load() -> Vec<Prototype> {
-        let mut file = File::open("prototype_data.json").expect("Unable to open file");
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)
-            .expect("Unable to read file");
+    // `source` is either the prototype JSON itself, a res:// or user:// path, or a path on disk.
+    //  Files may be in any PrototypeFormat, and binary files are recognised whatever they're
+    //  called.
+    pub fn load(source: &str) -> Result<Vec<Prototype>, String> {
+        if source.trim_start().starts_with('{') {
+            return Prototype::parse(source);
+        }
+
+        let bytes = read_file(source)?;
+        let protos = if prototype_binary::is_binary(&bytes) {
+            prototype_binary::decode(&bytes)
+        } else {
+            let text = String::from_utf8(bytes).map_err(|_| format!("'{}' isn't text", source))?;
+            match PrototypeFormat::from_path(source) {
+                PrototypeFormat::Toml => prototype_toml::parse(&text),
+                _ => Prototype::parse(&text),
+            }
+        };
+        protos.map_err(|e| format!("{}: {}", source, e))
+    }
+
+    // Entries that aren't valid prototypes are skipped, but there has to be at least one that is
+    pub fn parse(json: &str) -> Result<Vec<Prototype>, String> {
+        let parsed: Value =
+            serde_json::from_str(json).map_err(|e| format!("Invalid prototype JSON: {}", e))?;
+        let Some(obj) = parsed.as_object() else {
+            return Err("Prototype JSON must be an object of id to prototype".into());
+        };
 
         let mut protos: Vec<Prototype> = vec![];
-        let parsed: Value = serde_json::from_str(&contents).unwrap_or_default();
-
-        if let Some(obj) = parsed.as_object() {
-            for (key, value) in obj.iter() {
-                // Attempt to parse each value into a MyStruct
-                if let Some(parsed_struct) = Prototype::from_json_value(key.to_string(), value) {
-                    protos.push(parsed_struct)
-                } else {
-                    godot_print!("failed to parse Prototype '{}', ignoring", key);
-                }
+        for (key, value) in obj.iter() {
+            if let Some(parsed_struct) = Prototype::from_json_value(key.to_string(), value) {
+                protos.push(parsed_struct)
+            } else {
+                log_print!("failed to parse Prototype '{}', ignoring", key);
             }
-        } else {
-            println!("The parsed JSON is not an object");
         }
 
-        protos
+        if protos.is_empty() {
+            return Err("No valid prototypes found".into());
+        }
+        Ok(protos)
+    }
+
+    // Writes the same schema that parse reads, ordered by id with numbers compared by value
+    //  ("p2" before "p10"), so saving the same set twice gives the same file
+    pub fn serialize(protos: &[Prototype]) -> Result<String, String> {
+        let mut ordered: Vec<&Prototype> = protos.iter().collect();
+        ordered.sort_by(|a, b| natural_order(&a.id, &b.id));
+        serde_json::to_string_pretty(&PrototypeMap(ordered))
+            .map_err(|e| format!("Failed to serialize prototypes: {}", e))
+    }
+
+    // `path` is a res:// or user:// path, or a path on disk, and its extension picks the format
+    pub fn save(protos: &[Prototype], path: &str) -> Result<(), String> {
+        let contents = match PrototypeFormat::from_path(path) {
+            PrototypeFormat::Json => Prototype::serialize(protos)?.into_bytes(),
+            PrototypeFormat::Binary => prototype_binary::encode(protos)?,
+            PrototypeFormat::Toml => prototype_toml::serialize(protos)?.into_bytes(),
+        };
+        write_file(path, &contents)
+    }
+
+    // This prototype as a single entry of the JSON schema, without its id
+    pub fn to_json_value(&self) -> Value {
+        serde_json::to_value(PrototypeEntry::from(self)).unwrap_or_default()
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
     }
 
     pub fn compatible_with(&self, other_id: String, direction: Vector3i) -> bool {
@@ -140,3 +225,374 @@ impl Prototype {
             .any(|p| self.compatible_with(p.id.clone(), direction))
     }
 }
+
+// The files prototypes are read from and written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrototypeFormat {
+    // The schema exported from Blender, with every valid_neighbours list spelled out
+    Json,
+    // The same prototypes packed by prototype_binary, for loading quickly. Ends in ".lwfc".
+    Binary,
+    // A tileset written by hand, described in prototype_toml. Ends in ".toml".
+    Toml,
+}
+
+impl PrototypeFormat {
+    pub fn from_path(path: &str) -> Self {
+        let extension = path.rsplit_once('.').map(|(_, extension)| extension);
+        match extension.map(|e| e.to_ascii_lowercase()).as_deref() {
+            Some("lwfc") => PrototypeFormat::Binary,
+            Some("toml") => PrototypeFormat::Toml,
+            _ => PrototypeFormat::Json,
+        }
+    }
+
+    // Whether loading already expands rotations and derives adjacency, following the file's own
+    //  rules
+    pub fn is_authored(&self) -> bool {
+        *self == PrototypeFormat::Toml
+    }
+}
+
+fn read_file(path: &str) -> Result<Vec<u8>, String> {
+    if path.starts_with("res://") || path.starts_with("user://") {
+        let Some(mut file) = FileAccess::open(path.into(), ModeFlags::READ) else {
+            return Err(format!(
+                "Failed to open '{}': {:?}",
+                path,
+                FileAccess::get_open_error()
+            ));
+        };
+        let length = file.get_length() as i64;
+        return Ok(file.get_buffer(length).to_vec());
+    }
+    fs::read(path).map_err(|e| format!("Failed to read '{}': {}", path, e))
+}
+
+fn write_file(path: &str, contents: &[u8]) -> Result<(), String> {
+    if path.starts_with("res://") || path.starts_with("user://") {
+        let Some(mut file) = FileAccess::open(path.into(), ModeFlags::WRITE) else {
+            return Err(format!(
+                "Failed to open '{}': {:?}",
+                path,
+                FileAccess::get_open_error()
+            ));
+        };
+        file.store_buffer(PackedByteArray::from(contents));
+        return Ok(());
+    }
+    fs::write(path, contents).map_err(|e| format!("Failed to write '{}': {}", path, e))
+}
+
+// One prototype in the JSON schema, with the fields in the order the exporter writes them
+#[derive(Serialize)]
+struct PrototypeEntry<'a> {
+    mesh_name: &'a str,
+    mesh_rotation: i32,
+    #[serde(rename = "posX")]
+    pos_x: &'a str,
+    #[serde(rename = "negX")]
+    neg_x: &'a str,
+    #[serde(rename = "posY")]
+    pos_y: &'a str,
+    #[serde(rename = "negY")]
+    neg_y: &'a str,
+    #[serde(rename = "posZ")]
+    pos_z: &'a str,
+    #[serde(rename = "negZ")]
+    neg_z: &'a str,
+    constrain_to: &'a str,
+    constrain_from: &'a str,
+    #[serde(serialize_with = "serialize_weight")]
+    weight: f32,
+    no_id: i32,
+    no_id_sym: i32,
+    // Left out when empty, so untagged sets keep the exporter's schema
+    #[serde(skip_serializing_if = "<[String]>::is_empty")]
+    tags: &'a [String],
+    valid_neighbours: &'a [Vec<String>],
+}
+
+impl<'a> From<&'a Prototype> for PrototypeEntry<'a> {
+    fn from(proto: &'a Prototype) -> Self {
+        Self {
+            mesh_name: &proto.mesh_name,
+            mesh_rotation: proto.mesh_rotation,
+            pos_x: &proto.pos_x,
+            neg_x: &proto.neg_x,
+            pos_y: &proto.pos_y,
+            neg_y: &proto.neg_y,
+            pos_z: &proto.pos_z,
+            neg_z: &proto.neg_z,
+            constrain_to: &proto.constrain_to,
+            constrain_from: &proto.constrain_from,
+            weight: proto.weight,
+            no_id: proto.no_id,
+            no_id_sym: proto.no_id_sym,
+            tags: &proto.tags,
+            valid_neighbours: &proto.valid_neighbors,
+        }
+    }
+}
+
+// The exporter writes whole weights as integers
+fn serialize_weight<S: Serializer>(weight: &f32, serializer: S) -> Result<S::Ok, S::Error> {
+    if weight.fract() == 0.0 && weight.abs() < i64::MAX as f32 {
+        serializer.serialize_i64(*weight as i64)
+    } else {
+        serializer.serialize_f64(*weight as f64)
+    }
+}
+
+// Prototypes by id, in the order given
+struct PrototypeMap<'a>(Vec<&'a Prototype>);
+
+impl Serialize for PrototypeMap<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(
+            self.0
+                .iter()
+                .map(|proto| (&proto.id, PrototypeEntry::from(*proto))),
+        )
+    }
+}
+
+// Compares runs of digits by their value and everything else as text
+fn natural_order(a: &str, b: &str) -> Ordering {
+    let runs = |s: &str| {
+        let mut runs: Vec<(bool, String)> = vec![];
+        for c in s.chars() {
+            let digit = c.is_ascii_digit();
+            match runs.last_mut() {
+                Some((is_digits, run)) if *is_digits == digit => run.push(c),
+                _ => runs.push((digit, c.to_string())),
+            }
+        }
+        runs
+    };
+
+    for (a, b) in runs(a).into_iter().zip(runs(b)) {
+        let order = match (a, b) {
+            ((true, a), (true, b)) => {
+                let (a, b) = (a.trim_start_matches('0'), b.trim_start_matches('0'));
+                a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+            }
+            ((_, a), (_, b)) => a.cmp(&b),
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
+
+// Builds adjacency lists from each prototype's socket labels, as an alternative to the
+//  valid_neighbours table exported from Blender. Two prototypes fit along an axis when the socket
+//  on the facing side of one fits the socket on the facing side of the other:
+//  - "Ns" is symmetric, and fits another "Ns"
+//  - "N" is asymmetric, and fits its mirror image "Nf" (and "Nf" fits "N")
+//  - "vN_R" is a vertical socket at rotation R, and "vN_i" one that looks the same at every
+//    rotation. Both only fit the identical label.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SocketRules {
+    // The exported tables never put two rotations of the same mesh, or a prototype and itself,
+    //  next to each other. Off by default so the derived lists match them.
+    pub allow_same_mesh: bool,
+}
+
+impl SocketRules {
+    pub fn sockets_fit(a: &str, b: &str) -> bool {
+        if a.starts_with('v') || a.ends_with('s') {
+            return a == b;
+        }
+        match a.strip_suffix('f') {
+            Some(unflipped) => b == unflipped,
+            None => b.len() == a.len() + 1 && b.starts_with(a) && b.ends_with('f'),
+        }
+    }
+
+    // Overwrites every prototype's valid_neighbors
+    pub fn apply(&self, protos: &mut [Prototype]) {
+        let derived: Vec<Vec<Vec<String>>> = protos
+            .iter()
+            .map(|proto| {
+                let mut valid_neighbors = vec![vec![]; 6];
+                for other in protos.iter() {
+                    if !self.allow_same_mesh && other.mesh_name == proto.mesh_name {
+                        continue;
+                    }
+                    for (direction, (socket, facing)) in
+                        facing_sockets(proto, other).into_iter().enumerate()
+                    {
+                        if SocketRules::sockets_fit(socket, facing) {
+                            valid_neighbors[direction].push(other.id.clone());
+                        }
+                    }
+                }
+                valid_neighbors
+            })
+            .collect();
+
+        for (proto, valid_neighbors) in protos.iter_mut().zip(derived) {
+            proto.valid_neighbors = valid_neighbors;
+        }
+    }
+}
+
+// Each of `proto`'s sockets paired with the socket of `other` that would face it, in
+//  valid_neighbors order
+fn facing_sockets<'a>(proto: &'a Prototype, other: &'a Prototype) -> [(&'a str, &'a str); 6] {
+    let mut sockets = [("", ""); 6];
+    sockets[P_X] = (&proto.pos_x, &other.neg_x);
+    sockets[P_Y] = (&proto.pos_y, &other.neg_y);
+    sockets[N_X] = (&proto.neg_x, &other.pos_x);
+    sockets[N_Y] = (&proto.neg_y, &other.pos_y);
+    sockets[P_Z] = (&proto.pos_z, &other.neg_z);
+    sockets[N_Z] = (&proto.neg_z, &other.pos_z);
+    sockets
+}
+
+// Generates the four rotations about the vertical axis of each base module, so a tileset only has
+//  to author rotation 0. A quarter turn moves each side's socket round to the next side (+x to +y,
+//  +y to -x and so on), bumps the rotation of vertical "vN_R" sockets, and rotates both the
+//  directions and the entries of valid_neighbors.
+// Rotation 0 keeps the base module's id and rotation R is "<id>_R". Neighbour ids may use either.
+#[derive(Debug, Clone, Copy)]
+pub struct RotationExpansion {
+    // Drop rotations with the same sockets as an earlier rotation of the same module, such as all
+    //  but one rotation of a fully symmetric module. References to them go to the one that's kept.
+    pub dedupe_symmetric: bool,
+}
+
+impl Default for RotationExpansion {
+    fn default() -> Self {
+        Self {
+            dedupe_symmetric: true,
+        }
+    }
+}
+
+impl RotationExpansion {
+    pub fn apply(&self, bases: &[Prototype]) -> Vec<Prototype> {
+        let base_indices: HashMap<&str, usize> = bases
+            .iter()
+            .enumerate()
+            .map(|(i, base)| (base.id.as_str(), i))
+            .collect();
+
+        // The id that each (base, rotation) ends up as, and the rotations that are kept
+        let mut ids: HashMap<(usize, usize), String> = HashMap::new();
+        let mut rotations: Vec<(usize, usize, Prototype)> = vec![];
+        for (i, base) in bases.iter().enumerate() {
+            let mut kept: Vec<Prototype> = vec![];
+            for steps in 0..4 {
+                let rotated = rotate_sockets(base, steps);
+                let duplicate = kept
+                    .iter()
+                    .find(|other| self.dedupe_symmetric && sockets(other) == sockets(&rotated));
+                match duplicate {
+                    Some(other) => {
+                        ids.insert((i, steps), other.id.clone());
+                    }
+                    None => {
+                        ids.insert((i, steps), rotated.id.clone());
+                        kept.push(rotated.clone());
+                        rotations.push((i, steps, rotated));
+                    }
+                }
+            }
+        }
+
+        // A reference to rotation K of a module becomes rotation K + steps
+        let rotate_reference = |reference: &str, steps: usize| -> String {
+            let resolved = match base_indices.get(reference) {
+                Some(i) => Some((*i, 0)),
+                None => reference.rsplit_once('_').and_then(|(id, rotation)| {
+                    let rotation = rotation.parse::<usize>().ok().filter(|r| *r < 4)?;
+                    Some((*base_indices.get(id)?, rotation))
+                }),
+            };
+            match resolved {
+                Some((i, rotation)) => ids[&(i, (rotation + steps) % 4)].clone(),
+                // Left for the tileset validator to report
+                None => reference.to_string(),
+            }
+        };
+
+        rotations
+            .into_iter()
+            .map(|(i, steps, mut rotated)| {
+                let mut valid_neighbors: Vec<Vec<String>> = vec![vec![]; 6];
+                for (direction, neighbors) in bases[i].valid_neighbors.iter().enumerate().take(6) {
+                    let rotated_neighbors =
+                        &mut valid_neighbors[rotate_direction(direction, steps)];
+                    for neighbor in neighbors {
+                        let neighbor = rotate_reference(neighbor, steps);
+                        if !rotated_neighbors.contains(&neighbor) {
+                            rotated_neighbors.push(neighbor);
+                        }
+                    }
+                }
+                rotated.valid_neighbors = valid_neighbors;
+                rotated
+            })
+            .collect()
+    }
+}
+
+fn rotate_direction(direction: usize, steps: usize) -> usize {
+    match HORIZONTAL.iter().position(|d| *d == direction) {
+        Some(side) => HORIZONTAL[(side + steps) % 4],
+        None => direction,
+    }
+}
+
+// Everything but valid_neighbors, which depends on which rotations are kept
+fn rotate_sockets(base: &Prototype, steps: usize) -> Prototype {
+    let mut rotated = base.clone();
+    rotated.valid_neighbors = vec![];
+    if steps == 0 {
+        return rotated;
+    }
+
+    rotated.id = format!("{}_{}", base.id, steps);
+    rotated.mesh_rotation = (base.mesh_rotation + steps as i32) % 4;
+
+    let sides = [&base.pos_x, &base.pos_y, &base.neg_x, &base.neg_y];
+    let mut turned = [String::new(), String::new(), String::new(), String::new()];
+    for (side, socket) in sides.into_iter().enumerate() {
+        turned[(side + steps) % 4] = socket.clone();
+    }
+    let [pos_x, pos_y, neg_x, neg_y] = turned;
+    rotated.pos_x = pos_x;
+    rotated.pos_y = pos_y;
+    rotated.neg_x = neg_x;
+    rotated.neg_y = neg_y;
+    rotated.pos_z = rotate_vertical_socket(&base.pos_z, steps);
+    rotated.neg_z = rotate_vertical_socket(&base.neg_z, steps);
+    rotated
+}
+
+// "vN_R" becomes "vN_(R + steps)". Rotation invariant "vN_i" sockets and anything else stay as
+//  they are.
+fn rotate_vertical_socket(socket: &str, steps: usize) -> String {
+    let rotated = socket
+        .strip_prefix('v')
+        .and_then(|rest| rest.rsplit_once('_'))
+        .and_then(|(name, rotation)| {
+            let rotation = rotation.parse::<usize>().ok()?;
+            Some(format!("v{}_{}", name, (rotation + steps) % 4))
+        });
+    rotated.unwrap_or_else(|| socket.to_string())
+}
+
+fn sockets(proto: &Prototype) -> [&str; 6] {
+    [
+        &proto.pos_x,
+        &proto.pos_y,
+        &proto.neg_x,
+        &proto.neg_y,
+        &proto.pos_z,
+        &proto.neg_z,
+    ]
+}
@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+
+use super::prototype::Prototype;
+
+// A compact rule set, much quicker to load than the exported JSON. Every string is stored once
+//  in a table and referred to by index, and each valid_neighbors list is a bitmask over the
+//  prototypes. All numbers are little endian:
+//  - the magic "LWFC" and a u16 version
+//  - a u32 count of strings, each a u32 length and UTF-8 bytes
+//  - a u32 count of prototypes, each:
+//    - string indices (u32) of the id and mesh_name, then mesh_rotation (i32)
+//    - string indices of the sockets, in field order (posX, negX, posY, negY, posZ, negZ), and
+//      of constrain_to and constrain_from
+//    - weight (f32), no_id and no_id_sym (i32)
+//    - a u32 count of tags, and the string index of each
+//    - a u8 count of valid_neighbors lists, each one bit per prototype packed into u64 words
+// Neighbour lists come back in prototype order, without duplicates.
+pub const MAGIC: &[u8; 4] = b"LWFC";
+const VERSION: u16 = 1;
+
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+pub fn encode(protos: &[Prototype]) -> Result<Vec<u8>, String> {
+    let mut indices: HashMap<&str, usize> = HashMap::new();
+    for (i, proto) in protos.iter().enumerate() {
+        if indices.insert(&proto.id, i).is_some() {
+            return Err(format!("Duplicate prototype id '{}'", proto.id));
+        }
+    }
+
+    let mut strings = StringTable::default();
+    let mut body = vec![];
+    put_u32(&mut body, protos.len());
+    let words = protos.len().div_ceil(64);
+    for proto in protos.iter() {
+        put_u32(&mut body, strings.intern(&proto.id));
+        put_u32(&mut body, strings.intern(&proto.mesh_name));
+        body.extend(proto.mesh_rotation.to_le_bytes());
+        for field in [
+            &proto.pos_x,
+            &proto.neg_x,
+            &proto.pos_y,
+            &proto.neg_y,
+            &proto.pos_z,
+            &proto.neg_z,
+            &proto.constrain_to,
+            &proto.constrain_from,
+        ] {
+            put_u32(&mut body, strings.intern(field));
+        }
+        body.extend(proto.weight.to_le_bytes());
+        body.extend(proto.no_id.to_le_bytes());
+        body.extend(proto.no_id_sym.to_le_bytes());
+        put_u32(&mut body, proto.tags.len());
+        for tag in proto.tags.iter() {
+            put_u32(&mut body, strings.intern(tag));
+        }
+
+        let Ok(lists) = u8::try_from(proto.valid_neighbors.len()) else {
+            return Err(format!(
+                "'{}' has too many valid_neighbours lists ({})",
+                proto.id,
+                proto.valid_neighbors.len()
+            ));
+        };
+        body.push(lists);
+        for neighbors in proto.valid_neighbors.iter() {
+            let mut mask = vec![0u64; words];
+            for id in neighbors {
+                let Some(index) = indices.get(id.as_str()) else {
+                    return Err(format!(
+                        "'{}' allows unknown prototype '{}' as a neighbour",
+                        proto.id, id
+                    ));
+                };
+                mask[index / 64] |= 1 << (index % 64);
+            }
+            mask.iter().for_each(|word| body.extend(word.to_le_bytes()));
+        }
+    }
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(VERSION.to_le_bytes());
+    put_u32(&mut bytes, strings.strings.len());
+    for string in strings.strings.iter() {
+        put_u32(&mut bytes, string.len());
+        bytes.extend(string.as_bytes());
+    }
+    bytes.extend(body);
+    Ok(bytes)
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Vec<Prototype>, String> {
+    let mut reader = Reader { bytes, offset: 0 };
+    if reader.take(MAGIC.len())? != MAGIC {
+        return Err("Not a binary prototype file".into());
+    }
+    let version = u16::from_le_bytes(reader.array()?);
+    if version != VERSION {
+        return Err(format!("Unsupported binary prototype version {}", version));
+    }
+
+    let mut strings = vec![];
+    for _ in 0..reader.u32()? {
+        let len = reader.u32()? as usize;
+        let string = std::str::from_utf8(reader.take(len)?)
+            .map_err(|_| format!("String {} isn't valid UTF-8", strings.len()))?;
+        strings.push(string.to_string());
+    }
+    let string = |index: u32| -> Result<String, String> {
+        strings
+            .get(index as usize)
+            .cloned()
+            .ok_or_else(|| format!("String index {} out of range", index))
+    };
+
+    // Ids are needed to resolve the neighbour masks, so those are read in a second pass
+    let count = reader.u32()? as usize;
+    let words = count.div_ceil(64);
+    let mut protos = vec![];
+    let mut masks: Vec<Vec<Vec<u64>>> = vec![];
+    for _ in 0..count {
+        let id = string(reader.u32()?)?;
+        let mesh_name = string(reader.u32()?)?;
+        let mesh_rotation = reader.i32()?;
+        let pos_x = string(reader.u32()?)?;
+        let neg_x = string(reader.u32()?)?;
+        let pos_y = string(reader.u32()?)?;
+        let neg_y = string(reader.u32()?)?;
+        let pos_z = string(reader.u32()?)?;
+        let neg_z = string(reader.u32()?)?;
+        let constrain_to = string(reader.u32()?)?;
+        let constrain_from = string(reader.u32()?)?;
+        let weight = f32::from_le_bytes(reader.array()?);
+        let no_id = reader.i32()?;
+        let no_id_sym = reader.i32()?;
+        let mut tags = vec![];
+        for _ in 0..reader.u32()? {
+            tags.push(string(reader.u32()?)?);
+        }
+
+        let lists = reader.take(1)?[0];
+        let mut proto_masks = vec![];
+        for _ in 0..lists {
+            let mut mask = vec![];
+            for _ in 0..words {
+                mask.push(u64::from_le_bytes(reader.array()?));
+            }
+            proto_masks.push(mask);
+        }
+        masks.push(proto_masks);
+
+        protos.push(Prototype {
+            id,
+            mesh_name,
+            mesh_rotation,
+            pos_x,
+            neg_x,
+            pos_y,
+            neg_y,
+            pos_z,
+            neg_z,
+            constrain_to,
+            constrain_from,
+            weight,
+            no_id,
+            no_id_sym,
+            tags,
+            valid_neighbors: vec![],
+        });
+    }
+    if reader.offset != bytes.len() {
+        return Err(format!(
+            "{} unexpected bytes after the last prototype",
+            bytes.len() - reader.offset
+        ));
+    }
+
+    let ids: Vec<String> = protos.iter().map(|p| p.id.clone()).collect();
+    for (proto, proto_masks) in protos.iter_mut().zip(masks) {
+        for mask in proto_masks {
+            let mut neighbors = vec![];
+            for (i, word) in mask.iter().enumerate() {
+                for bit in (0..64).filter(|bit| word & (1 << bit) != 0) {
+                    let Some(id) = ids.get(i * 64 + bit) else {
+                        return Err(format!(
+                            "'{}' allows prototype {}, but there are only {}",
+                            proto.id,
+                            i * 64 + bit,
+                            ids.len()
+                        ));
+                    };
+                    neighbors.push(id.clone());
+                }
+            }
+            proto.valid_neighbors.push(neighbors);
+        }
+    }
+    Ok(protos)
+}
+
+#[derive(Default)]
+struct StringTable<'a> {
+    strings: Vec<&'a str>,
+    indices: HashMap<&'a str, usize>,
+}
+
+impl<'a> StringTable<'a> {
+    fn intern(&mut self, string: &'a str) -> usize {
+        *self.indices.entry(string).or_insert_with(|| {
+            self.strings.push(string);
+            self.strings.len() - 1
+        })
+    }
+}
+
+fn put_u32(bytes: &mut Vec<u8>, value: usize) {
+    bytes.extend((value as u32).to_le_bytes());
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| format!("Binary prototypes end early, at byte {}", self.offset))?;
+        let taken = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.take(N)?.try_into().unwrap_or([0; N]))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_le_bytes(self.array()?))
+    }
+}
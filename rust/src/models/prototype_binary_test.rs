@@ -0,0 +1,89 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::models::prototype::Prototype;
+    use crate::models::prototype_binary::{decode, encode, is_binary};
+
+    fn exported() -> Vec<Prototype> {
+        Prototype::parse(include_str!("../../../godot/prototype_data.json")).unwrap()
+    }
+
+    // The first few exported prototypes, allowing each other on every side
+    fn few(count: usize) -> Vec<Prototype> {
+        let mut protos: Vec<Prototype> = exported().into_iter().take(count).collect();
+        let ids: Vec<String> = protos.iter().map(|p| p.id.clone()).collect();
+        for proto in protos.iter_mut() {
+            proto.valid_neighbors = vec![ids.clone(); 6];
+        }
+        protos
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let protos = exported();
+        let bytes = encode(&protos).unwrap();
+        assert!(is_binary(&bytes));
+        // Far smaller than the JSON it came from
+        assert!(bytes.len() * 10 < include_str!("../../../godot/prototype_data.json").len());
+
+        let decoded = decode(&bytes).unwrap();
+        assert_eq!(protos.len(), decoded.len());
+        for (expected, actual) in protos.iter().zip(decoded.iter()) {
+            let mut without_neighbors = expected.clone();
+            without_neighbors.valid_neighbors = actual.valid_neighbors.clone();
+            assert_eq!(&without_neighbors, actual);
+
+            assert_eq!(expected.valid_neighbors.len(), actual.valid_neighbors.len());
+            for (expected, actual) in expected.valid_neighbors.iter().zip(&actual.valid_neighbors) {
+                let expected: HashSet<&String> = expected.iter().collect();
+                let actual: HashSet<&String> = actual.iter().collect();
+                assert_eq!(expected, actual, "Test Failed: {}", without_neighbors.id);
+            }
+        }
+    }
+
+    #[test]
+    fn test_keeps_tags_and_missing_neighbour_lists() {
+        let mut protos = few(2);
+        protos[0].tags = vec!["walkable".into(), "grass".into()];
+        protos[0].weight = 2.5;
+        for proto in protos.iter_mut() {
+            proto.valid_neighbors = vec![];
+        }
+        protos[1].valid_neighbors = vec![vec![protos[0].id.clone()], vec![], vec![], vec![]];
+
+        assert_eq!(protos, decode(&encode(&protos).unwrap()).unwrap());
+    }
+
+    #[test]
+    fn test_encode_errors() {
+        let mut protos = few(2);
+        protos[0].valid_neighbors[0].push("p999".into());
+        assert!(encode(&protos).unwrap_err().contains("p999"));
+
+        let mut protos = few(2);
+        protos[1].id = protos[0].id.clone();
+        assert!(encode(&protos).unwrap_err().contains("Duplicate"));
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let bytes = encode(&few(3)).unwrap();
+
+        let mut wrong_version = bytes.clone();
+        wrong_version[4] = 9;
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+
+        let tests = [
+            ("not binary", b"{ \"p0\": {} }".to_vec()),
+            ("wrong version", wrong_version),
+            ("truncated", bytes[..bytes.len() - 1].to_vec()),
+            ("trailing bytes", trailing),
+        ];
+        for (name, bytes) in tests {
+            assert!(decode(&bytes).is_err(), "Test Failed: {}", name);
+        }
+    }
+}
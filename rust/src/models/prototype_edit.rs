@@ -0,0 +1,130 @@
+use serde_json::Value;
+
+use super::prototype::{Prototype, OPPOSITE};
+
+// Edits to a prototype set that keep its valid_neighbours symmetric: whenever A allows B on one
+//  side, B allows A on the opposite side. Directions are valid_neighbours indices.
+
+// `entry` is a prototype in the JSON schema. Its valid_neighbours may only name prototypes that
+//  already exist, or the new prototype itself, and each is given the matching entry back.
+pub fn add_prototype(protos: &mut Vec<Prototype>, id: &str, entry: &Value) -> Result<(), String> {
+    if protos.iter().any(|p| p.id == id) {
+        return Err(format!("Prototype '{}' already exists", id));
+    }
+    let mut proto = Prototype::from_json_value(id.into(), entry)
+        .ok_or(format!("Invalid prototype data for '{}'", id))?;
+    if proto.valid_neighbors.len() > 6 {
+        return Err(format!("'{}' has more than 6 valid_neighbours lists", id));
+    }
+    proto.valid_neighbors.resize(6, vec![]);
+    for neighbor in proto.valid_neighbors.iter().flatten() {
+        if neighbor != id && !protos.iter().any(|p| p.id == *neighbor) {
+            return Err(format!("Unknown prototype '{}'", neighbor));
+        }
+    }
+
+    let adjacencies: Vec<(usize, String)> = proto
+        .valid_neighbors
+        .iter()
+        .enumerate()
+        .flat_map(|(direction, ids)| ids.iter().map(move |n| (direction, n.clone())))
+        .collect();
+    protos.push(proto);
+    for (direction, neighbor) in adjacencies {
+        add_adjacency(protos, id, &neighbor, direction)?;
+    }
+    Ok(())
+}
+
+// `changes` is any subset of the JSON schema's fields, except valid_neighbours, which is changed
+//  through add_adjacency and remove_adjacency
+pub fn update_prototype(protos: &mut [Prototype], id: &str, changes: &Value) -> Result<(), String> {
+    let index = find(protos, id)?;
+    let Value::Object(changes) = changes else {
+        return Err("Prototype changes must be an object".into());
+    };
+    if changes.contains_key("valid_neighbours") {
+        return Err("Change valid_neighbours with add_adjacency and remove_adjacency".into());
+    }
+
+    let mut entry = protos[index].to_json_value();
+    if let Value::Object(fields) = &mut entry {
+        for (key, value) in changes {
+            fields.insert(key.clone(), value.clone());
+        }
+    }
+    protos[index] = Prototype::from_json_value(id.into(), &entry)
+        .ok_or(format!("Invalid prototype data for '{}'", id))?;
+    Ok(())
+}
+
+// Also removes it from every other prototype's valid_neighbours
+pub fn remove_prototype(protos: &mut Vec<Prototype>, id: &str) -> Result<(), String> {
+    let index = find(protos, id)?;
+    protos.remove(index);
+    for proto in protos.iter_mut() {
+        for neighbors in proto.valid_neighbors.iter_mut() {
+            neighbors.retain(|n| n != id);
+        }
+    }
+    Ok(())
+}
+
+// Lets `b` sit on the given side of `a`, and `a` on the opposite side of `b`
+pub fn add_adjacency(
+    protos: &mut [Prototype],
+    a: &str,
+    b: &str,
+    direction: usize,
+) -> Result<(), String> {
+    let (a_index, b_index) = find_pair(protos, a, b, direction)?;
+    for (index, other, direction) in [(a_index, b, direction), (b_index, a, OPPOSITE[direction])] {
+        let neighbors = &mut protos[index].valid_neighbors[direction];
+        if !neighbors.iter().any(|n| n == other) {
+            neighbors.push(other.into());
+        }
+    }
+    Ok(())
+}
+
+pub fn remove_adjacency(
+    protos: &mut [Prototype],
+    a: &str,
+    b: &str,
+    direction: usize,
+) -> Result<(), String> {
+    let (a_index, b_index) = find_pair(protos, a, b, direction)?;
+    for (index, other, direction) in [(a_index, b, direction), (b_index, a, OPPOSITE[direction])] {
+        protos[index].valid_neighbors[direction].retain(|n| n != other);
+    }
+    Ok(())
+}
+
+fn find(protos: &[Prototype], id: &str) -> Result<usize, String> {
+    protos
+        .iter()
+        .position(|p| p.id == id)
+        .ok_or(format!("Unknown prototype '{}'", id))
+}
+
+// Both prototypes, with room for every direction's valid_neighbours
+fn find_pair(
+    protos: &mut [Prototype],
+    a: &str,
+    b: &str,
+    direction: usize,
+) -> Result<(usize, usize), String> {
+    if direction >= 6 {
+        return Err(format!(
+            "Direction must be between 0 and 5, got {}",
+            direction
+        ));
+    }
+    let (a_index, b_index) = (find(protos, a)?, find(protos, b)?);
+    for index in [a_index, b_index] {
+        if protos[index].valid_neighbors.len() < 6 {
+            protos[index].valid_neighbors.resize(6, vec![]);
+        }
+    }
+    Ok((a_index, b_index))
+}
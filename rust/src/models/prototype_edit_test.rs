@@ -0,0 +1,126 @@
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::models::prototype::Prototype;
+    use crate::models::prototype_edit::{
+        add_adjacency, add_prototype, remove_adjacency, remove_prototype, update_prototype,
+    };
+
+    const P_X: usize = 0;
+    const N_X: usize = 2;
+    const P_Z: usize = 4;
+    const N_Z: usize = 5;
+
+    fn entry(neighbours: serde_json::Value) -> serde_json::Value {
+        json!({
+            "mesh_name": "mesh", "mesh_rotation": 0,
+            "posX": "0s", "negX": "0s", "posY": "0s", "negY": "0s", "posZ": "-1", "negZ": "-1",
+            "constrain_to": "", "constrain_from": "", "weight": 1,
+            "valid_neighbours": neighbours
+        })
+    }
+
+    fn neighbors<'a>(protos: &'a [Prototype], id: &str, direction: usize) -> Vec<&'a str> {
+        let proto = protos.iter().find(|p| p.id == id).unwrap();
+        proto.valid_neighbors[direction]
+            .iter()
+            .map(|n| n.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_add_prototype_is_symmetric() {
+        let mut protos = vec![];
+        add_prototype(&mut protos, "ground", &entry(json!([]))).unwrap();
+        add_prototype(
+            &mut protos,
+            "tree",
+            &entry(json!([["tree"], [], [], [], [], ["ground"]])),
+        )
+        .unwrap();
+
+        assert_eq!(vec!["tree"], neighbors(&protos, "tree", P_X));
+        assert_eq!(vec!["tree"], neighbors(&protos, "tree", N_X));
+        assert_eq!(vec!["ground"], neighbors(&protos, "tree", N_Z));
+        assert_eq!(vec!["tree"], neighbors(&protos, "ground", P_Z));
+
+        let tests = [
+            ("duplicate id", "tree", entry(json!([]))),
+            ("unknown neighbour", "rock", entry(json!([["boulder"]]))),
+            ("missing fields", "rock", json!({ "mesh_name": "rock" })),
+        ];
+        for (name, id, data) in tests {
+            assert!(
+                add_prototype(&mut protos, id, &data).is_err(),
+                "Test Failed: {}",
+                name
+            );
+        }
+        assert_eq!(2, protos.len());
+    }
+
+    #[test]
+    fn test_update_prototype() {
+        let mut protos = vec![];
+        add_prototype(&mut protos, "tree", &entry(json!([["tree"]]))).unwrap();
+
+        update_prototype(
+            &mut protos,
+            "tree",
+            &json!({ "weight": 3.5, "tags": ["forest"] }),
+        )
+        .unwrap();
+        assert_eq!(3.5, protos[0].weight);
+        assert_eq!(vec!["forest"], protos[0].tags);
+        assert_eq!("mesh", protos[0].mesh_name);
+        assert_eq!(vec!["tree"], neighbors(&protos, "tree", P_X));
+
+        let tests = [
+            ("unknown id", "rock", json!({ "weight": 1 })),
+            ("neighbours", "tree", json!({ "valid_neighbours": [] })),
+            ("wrong type", "tree", json!({ "weight": "heavy" })),
+            ("not an object", "tree", json!(5)),
+        ];
+        for (name, id, changes) in tests {
+            assert!(
+                update_prototype(&mut protos, id, &changes).is_err(),
+                "Test Failed: {}",
+                name
+            );
+        }
+        assert_eq!(3.5, protos[0].weight);
+    }
+
+    #[test]
+    fn test_adjacency() {
+        let mut protos = vec![];
+        add_prototype(&mut protos, "a", &entry(json!([]))).unwrap();
+        add_prototype(&mut protos, "b", &entry(json!([]))).unwrap();
+
+        // Adding twice doesn't duplicate
+        add_adjacency(&mut protos, "a", "b", P_X).unwrap();
+        add_adjacency(&mut protos, "a", "b", P_X).unwrap();
+        assert_eq!(vec!["b"], neighbors(&protos, "a", P_X));
+        assert_eq!(vec!["a"], neighbors(&protos, "b", N_X));
+
+        remove_adjacency(&mut protos, "b", "a", N_X).unwrap();
+        assert!(neighbors(&protos, "a", P_X).is_empty());
+        assert!(neighbors(&protos, "b", N_X).is_empty());
+
+        assert!(add_adjacency(&mut protos, "a", "b", 6).is_err());
+        assert!(add_adjacency(&mut protos, "a", "c", P_X).is_err());
+    }
+
+    #[test]
+    fn test_remove_prototype() {
+        let mut protos = vec![];
+        add_prototype(&mut protos, "a", &entry(json!([]))).unwrap();
+        add_prototype(&mut protos, "b", &entry(json!([["a"], [], ["a"]]))).unwrap();
+
+        remove_prototype(&mut protos, "b").unwrap();
+        assert_eq!(1, protos.len());
+        assert!(protos[0].valid_neighbors.iter().all(|n| n.is_empty()));
+        assert!(remove_prototype(&mut protos, "b").is_err());
+    }
+}
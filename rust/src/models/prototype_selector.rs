@@ -0,0 +1,32 @@
+use std::fmt;
+
+use super::prototype::Prototype;
+
+// Picks out a group of prototypes for commands that change several at once
+#[derive(Debug, Clone, PartialEq)]
+pub enum PrototypeSelector {
+    Id(String),
+    // Every rotation of a mesh
+    Mesh(String),
+    Tag(String),
+}
+
+impl PrototypeSelector {
+    pub fn matches(&self, proto: &Prototype) -> bool {
+        match self {
+            PrototypeSelector::Id(id) => proto.id == *id,
+            PrototypeSelector::Mesh(mesh_name) => proto.mesh_name == *mesh_name,
+            PrototypeSelector::Tag(tag) => proto.has_tag(tag),
+        }
+    }
+}
+
+impl fmt::Display for PrototypeSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrototypeSelector::Id(id) => write!(f, "prototype '{}'", id),
+            PrototypeSelector::Mesh(mesh_name) => write!(f, "mesh '{}'", mesh_name),
+            PrototypeSelector::Tag(tag) => write!(f, "tag '{}'", tag),
+        }
+    }
+}
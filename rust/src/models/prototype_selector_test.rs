@@ -0,0 +1,43 @@
+#[cfg(test)]
+mod tests {
+    use crate::models::prototype::Prototype;
+    use crate::models::prototype_selector::PrototypeSelector;
+
+    const RAMPS: &str = r#"{
+        "ramp": {
+            "mesh_name": "ramp_mesh", "mesh_rotation": 0,
+            "posX": "0", "negX": "0f", "posY": "0s", "negY": "0s", "posZ": "-1", "negZ": "-1",
+            "constrain_to": "", "constrain_from": "", "weight": 1.0
+        },
+        "ramp_1": {
+            "mesh_name": "ramp_mesh", "mesh_rotation": 1,
+            "posX": "0s", "negX": "0s", "posY": "0", "negY": "0f", "posZ": "-1", "negZ": "-1",
+            "constrain_to": "", "constrain_from": "", "weight": 1.0
+        }
+    }"#;
+
+    #[test]
+    fn test_matches() {
+        let mut protos = Prototype::parse(RAMPS).unwrap();
+        protos.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let tests = [
+            (PrototypeSelector::Id("ramp".into()), vec!["ramp"]),
+            (PrototypeSelector::Id("ramp_mesh".into()), vec![]),
+            (
+                PrototypeSelector::Mesh("ramp_mesh".into()),
+                vec!["ramp", "ramp_1"],
+            ),
+            (PrototypeSelector::Mesh("ramp".into()), vec![]),
+        ];
+
+        for (selector, expected) in tests {
+            let matched: Vec<&str> = protos
+                .iter()
+                .filter(|p| selector.matches(p))
+                .map(|p| p.id.as_str())
+                .collect();
+            assert_eq!(expected, matched, "Test Failed: {}", selector);
+        }
+    }
+}
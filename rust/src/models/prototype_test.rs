@@ -0,0 +1,343 @@
+#[cfg(test)]
+mod tests {
+    use godot::builtin::Vector3i;
+
+    use std::collections::HashSet;
+
+    use crate::models::prototype::{Prototype, RotationExpansion, SocketRules};
+
+    const GRASS: &str = r#"{
+        "grass": {
+            "mesh_name": "grass_mesh",
+            "mesh_rotation": 1,
+            "posX": "0s", "negX": "0s", "posY": "0s", "negY": "0s", "posZ": "-1", "negZ": "-1",
+            "constrain_to": "", "constrain_from": "",
+            "weight": 2.5,
+            "valid_neighbours": [["grass"], ["grass"], ["grass"], ["grass"], [], []]
+        }
+    }"#;
+
+    #[test]
+    fn test_parse() {
+        let protos = Prototype::parse(GRASS).unwrap();
+        assert_eq!(1, protos.len());
+
+        let grass = &protos[0];
+        assert_eq!("grass", grass.id);
+        assert_eq!("grass_mesh", grass.mesh_name);
+        assert_eq!(1, grass.mesh_rotation);
+        assert_eq!(2.5, grass.weight);
+        assert_eq!(0, grass.no_id);
+        assert!(grass.compatible_with("grass".into(), Vector3i::RIGHT));
+        assert!(!grass.compatible_with("grass".into(), Vector3i::UP));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let tests = [
+            ("not json", "{ not json"),
+            ("not an object", "[1, 2, 3]"),
+            ("no prototypes", "{}"),
+        ];
+
+        for (name, json) in tests {
+            assert!(Prototype::parse(json).is_err(), "Test Failed: {}", name);
+        }
+    }
+
+    #[test]
+    fn test_load_sources() {
+        // JSON is used as it is, even with leading whitespace
+        let protos = Prototype::load(&format!("\n  {}", GRASS)).unwrap();
+        assert_eq!("grass", protos[0].id);
+
+        let path = std::env::temp_dir().join("lwfc_prototype_test.json");
+        std::fs::write(&path, GRASS).unwrap();
+        let protos = Prototype::load(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!("grass", protos[0].id);
+
+        let missing = Prototype::load("/no/such/prototype_data.json");
+        assert!(missing
+            .unwrap_err()
+            .contains("/no/such/prototype_data.json"));
+    }
+
+    #[test]
+    fn test_save_and_load_formats() {
+        // TOML only keeps neighbours that follow from the sockets, and grass can't sit by itself
+        let mut protos = Prototype::parse(GRASS).unwrap();
+        protos[0].valid_neighbors = vec![vec![]; 6];
+        let dir = std::env::temp_dir();
+
+        for name in ["lwfc_formats_test.lwfc", "lwfc_formats_test.toml"] {
+            let path = dir.join(name);
+            let path = path.to_str().unwrap();
+            Prototype::save(&protos, path).unwrap();
+            let loaded = Prototype::load(path).unwrap();
+            std::fs::remove_file(path).unwrap();
+            assert_eq!(protos, loaded, "Test Failed: {}", name);
+        }
+
+        // Binary files are recognised by their contents, whatever they're called
+        let path = dir.join("lwfc_formats_test.lwfc");
+        Prototype::save(&protos, path.to_str().unwrap()).unwrap();
+        let renamed = dir.join("lwfc_formats_test_binary.json");
+        std::fs::rename(&path, &renamed).unwrap();
+        let loaded = Prototype::load(renamed.to_str().unwrap());
+        std::fs::remove_file(&renamed).unwrap();
+        assert_eq!(protos, loaded.unwrap());
+    }
+
+    #[test]
+    fn test_sockets_fit() {
+        let tests = [
+            ("0s", "0s", true),
+            ("0s", "1s", false),
+            ("0s", "0sf", false),
+            ("1", "1f", true),
+            ("1f", "1", true),
+            ("1", "1", false),
+            ("1f", "1f", false),
+            ("1", "11f", false),
+            ("-1", "-1f", true),
+            ("v0_1", "v0_1", true),
+            ("v0_1", "v0_2", false),
+            ("v0_i", "v0_i", true),
+        ];
+
+        for (a, b, expected) in tests {
+            assert_eq!(
+                expected,
+                SocketRules::sockets_fit(a, b),
+                "Test Failed: {} next to {}",
+                a,
+                b
+            );
+        }
+    }
+
+    #[test]
+    fn test_apply_matches_exported_neighbours() {
+        let json = include_str!("../../../godot/prototype_data.json");
+        let exported = Prototype::parse(json).unwrap();
+
+        let mut derived = exported.clone();
+        SocketRules::default().apply(&mut derived);
+
+        for (exported, derived) in exported.iter().zip(derived.iter()) {
+            for (direction, (expected, actual)) in exported
+                .valid_neighbors
+                .iter()
+                .zip(derived.valid_neighbors.iter())
+                .enumerate()
+            {
+                let mut expected = expected.clone();
+                let mut actual = actual.clone();
+                expected.sort();
+                actual.sort();
+                assert_eq!(
+                    expected, actual,
+                    "Test Failed: {} in direction {}",
+                    exported.id, direction
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_apply_same_mesh() {
+        let json = r#"{
+            "a0": {
+                "mesh_name": "a", "mesh_rotation": 0,
+                "posX": "0s", "negX": "0s", "posY": "0s", "negY": "0s", "posZ": "-1", "negZ": "-1",
+                "constrain_to": "", "constrain_from": "", "weight": 1
+            },
+            "a1": {
+                "mesh_name": "a", "mesh_rotation": 1,
+                "posX": "0s", "negX": "0s", "posY": "0s", "negY": "0s", "posZ": "-1", "negZ": "-1",
+                "constrain_to": "", "constrain_from": "", "weight": 1
+            },
+            "b0": {
+                "mesh_name": "b", "mesh_rotation": 0,
+                "posX": "0s", "negX": "1s", "posY": "0s", "negY": "0s", "posZ": "-1f", "negZ": "-1f",
+                "constrain_to": "", "constrain_from": "", "weight": 1
+            }
+        }"#;
+        let mut protos = Prototype::parse(json).unwrap();
+        assert!(protos.iter().all(|p| p.valid_neighbors.is_empty()));
+
+        SocketRules::default().apply(&mut protos);
+        let a0 = &protos[0];
+        assert!(a0.valid_neighbors[0].is_empty());
+        assert_eq!(vec!["b0".to_string()], a0.valid_neighbors[2]);
+        assert_eq!(vec!["b0".to_string()], a0.valid_neighbors[4]);
+
+        SocketRules {
+            allow_same_mesh: true,
+        }
+        .apply(&mut protos);
+        let a0 = &protos[0];
+        assert_eq!(
+            vec!["a0".to_string(), "a1".to_string()],
+            a0.valid_neighbors[0]
+        );
+        assert_eq!(
+            vec!["a0".to_string(), "a1".to_string(), "b0".to_string()],
+            a0.valid_neighbors[2]
+        );
+        assert!(a0.compatible_with("b0".into(), Vector3i::UP));
+    }
+
+    #[test]
+    fn test_expansion_matches_exported_rotations() {
+        let json = include_str!("../../../godot/prototype_data.json");
+        let exported = Prototype::parse(json).unwrap();
+
+        // The export names rotation K of module pN as p(N + K), which expansion calls pN_K
+        let rename = |id: &str| -> String {
+            match id.strip_prefix('p').and_then(|n| n.parse::<i32>().ok()) {
+                Some(n) if n >= 0 && n % 4 != 0 => format!("p{}_{}", n / 4 * 4, n % 4),
+                _ => id.to_string(),
+            }
+        };
+        let rename_all = |proto: &Prototype| -> Prototype {
+            let mut renamed = proto.clone();
+            renamed.id = rename(&proto.id);
+            renamed.valid_neighbors = proto
+                .valid_neighbors
+                .iter()
+                .map(|neighbors| neighbors.iter().map(|id| rename(id)).collect())
+                .collect();
+            renamed
+        };
+
+        let expected: Vec<Prototype> = exported
+            .iter()
+            .filter(|p| p.id != "p-1")
+            .map(rename_all)
+            .collect();
+        let bases: Vec<Prototype> = expected
+            .iter()
+            .filter(|p| p.mesh_rotation == 0)
+            .cloned()
+            .collect();
+
+        let expanded = RotationExpansion {
+            dedupe_symmetric: false,
+        }
+        .apply(&bases);
+        assert_eq!(expected.len(), expanded.len());
+
+        for proto in expanded.iter() {
+            let expected = expected.iter().find(|p| p.id == proto.id).unwrap();
+            assert_eq!(expected.mesh_name, proto.mesh_name, "{}", proto.id);
+            assert_eq!(expected.mesh_rotation, proto.mesh_rotation, "{}", proto.id);
+            assert_eq!(
+                [
+                    &expected.pos_x,
+                    &expected.pos_y,
+                    &expected.neg_x,
+                    &expected.neg_y
+                ],
+                [&proto.pos_x, &proto.pos_y, &proto.neg_x, &proto.neg_y],
+                "Test Failed: sockets of {}",
+                proto.id
+            );
+            for direction in 0..6 {
+                let expected: HashSet<&String> =
+                    expected.valid_neighbors[direction].iter().collect();
+                let actual: HashSet<&String> = proto.valid_neighbors[direction].iter().collect();
+                assert_eq!(
+                    expected, actual,
+                    "Test Failed: {} in direction {}",
+                    proto.id, direction
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_expansion_dedupes_symmetric_modules() {
+        let json = r#"{
+            "cross": {
+                "mesh_name": "cross", "mesh_rotation": 0,
+                "posX": "1s", "negX": "1s", "posY": "1s", "negY": "1s", "posZ": "v0_i", "negZ": "-1",
+                "constrain_to": "", "constrain_from": "", "weight": 1,
+                "valid_neighbours": [["line"], ["line_1"], [], [], [], []]
+            },
+            "line": {
+                "mesh_name": "line", "mesh_rotation": 0,
+                "posX": "1s", "negX": "1s", "posY": "0s", "negY": "0s", "posZ": "v1_i", "negZ": "-1",
+                "constrain_to": "", "constrain_from": "", "weight": 1,
+                "valid_neighbours": [["cross"], [], [], [], [], []]
+            },
+            "post": {
+                "mesh_name": "post", "mesh_rotation": 0,
+                "posX": "2s", "negX": "2s", "posY": "2s", "negY": "2s", "posZ": "v2_0", "negZ": "-1",
+                "constrain_to": "", "constrain_from": "", "weight": 1
+            }
+        }"#;
+        let bases = Prototype::parse(json).unwrap();
+        let expanded = RotationExpansion::default().apply(&bases);
+
+        let ids: Vec<&str> = expanded.iter().map(|p| p.id.as_str()).collect();
+        // The post's top only looks the same at one rotation, so all four are kept
+        assert_eq!(
+            vec!["cross", "line", "line_1", "post", "post_1", "post_2", "post_3"],
+            ids
+        );
+
+        let line_1 = &expanded[2];
+        assert_eq!(1, line_1.mesh_rotation);
+        assert_eq!(
+            ["0s", "1s", "0s", "1s"],
+            [&line_1.pos_x, &line_1.pos_y, &line_1.neg_x, &line_1.neg_y]
+        );
+        assert_eq!("v1_i", line_1.pos_z);
+        assert_eq!("v2_3", expanded[6].pos_z);
+        // +x became +y, and the cross it allowed there is the same at every rotation
+        assert_eq!(vec!["cross".to_string()], line_1.valid_neighbors[1]);
+
+        // Only the kept rotation of the cross contributes neighbours
+        let cross = &expanded[0];
+        assert_eq!(vec!["line".to_string()], cross.valid_neighbors[0]);
+        assert_eq!(vec!["line_1".to_string()], cross.valid_neighbors[1]);
+    }
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let protos = Prototype::parse(include_str!("../../../godot/prototype_data.json")).unwrap();
+        let json = Prototype::serialize(&protos).unwrap();
+
+        let mut reparsed = Prototype::parse(&json).unwrap();
+        let mut expected = protos.clone();
+        reparsed.sort_by(|a, b| a.id.cmp(&b.id));
+        expected.sort_by(|a, b| a.id.cmp(&b.id));
+        assert_eq!(expected, reparsed);
+
+        // Saving again gives the same file, whatever order the prototypes are in
+        let mut shuffled = protos.clone();
+        shuffled.reverse();
+        assert_eq!(json, Prototype::serialize(&shuffled).unwrap());
+
+        // Ordered like the exported file, with its field names and whole weights
+        assert!(json.starts_with("{\n  \"p0\": {\n    \"mesh_name\": \"wfc_module_0\","));
+        let position = |id: &str| json.find(&format!("\"{}\": {{", id)).unwrap();
+        assert!(position("p2") < position("p10"));
+        assert!(position("p347") < position("p-1"));
+        assert!(json.contains("\"posX\": \"0s\""));
+        assert!(json.contains("\"weight\": 10,"));
+        assert!(!json.contains("\"tags\""));
+    }
+
+    #[test]
+    fn test_serialize_keeps_tags_and_fractional_weights() {
+        let mut protos = Prototype::parse(GRASS).unwrap();
+        protos[0].tags = vec!["walkable".into()];
+        let json = Prototype::serialize(&protos).unwrap();
+
+        assert!(json.contains("\"weight\": 2.5,"));
+        assert_eq!(protos, Prototype::parse(&json).unwrap());
+    }
+}
@@ -0,0 +1,203 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use super::prototype::{Prototype, RotationExpansion, SocketRules};
+
+// A tileset written by hand: each module lists its sockets, and which modules fit together is
+//  worked out from them, so there are no valid_neighbours lists to keep in sync.
+//
+//  [rules]
+//  expand_rotations = true    # generate rotations 1 to 3 of each module, as "<id>_R"
+//  dedupe_symmetric = true    # drop rotations that look the same as an earlier one
+//  allow_same_mesh = false    # let a mesh sit next to itself
+//
+//  [[module]]
+//  id = "corner"
+//  mesh = "wfc_module_4"
+//  weight = 10                # defaults to 1
+//  tags = ["walkable"]        # optional
+//  posX = "0s"
+//  negX = "1"
+//  posY = "1f"
+//  negY = "0s"
+//  posZ = "-1"
+//  negZ = "-1"
+//
+// Modules also take the optional "rotation", "constrain_to" and "constrain_from" of the JSON
+//  schema. Sockets are described on SocketRules.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct TilesetToml {
+    #[serde(default)]
+    rules: Rules,
+    #[serde(default, rename = "module")]
+    modules: Vec<Module>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+struct Rules {
+    expand_rotations: bool,
+    dedupe_symmetric: bool,
+    allow_same_mesh: bool,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            expand_rotations: false,
+            dedupe_symmetric: RotationExpansion::default().dedupe_symmetric,
+            allow_same_mesh: SocketRules::default().allow_same_mesh,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct Module {
+    id: String,
+    mesh: String,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    rotation: i32,
+    #[serde(default = "default_weight")]
+    weight: f32,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(rename = "posX")]
+    pos_x: String,
+    #[serde(rename = "negX")]
+    neg_x: String,
+    #[serde(rename = "posY")]
+    pos_y: String,
+    #[serde(rename = "negY")]
+    neg_y: String,
+    #[serde(rename = "posZ")]
+    pos_z: String,
+    #[serde(rename = "negZ")]
+    neg_z: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    constrain_to: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    constrain_from: String,
+    // Kept so sets converted from the export keep them, but nothing reads them
+    #[serde(default, skip_serializing_if = "is_zero")]
+    no_id: i32,
+    #[serde(default, skip_serializing_if = "is_zero")]
+    no_id_sym: i32,
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+fn is_zero(value: &i32) -> bool {
+    *value == 0
+}
+
+// The finished prototypes, with rotations expanded and adjacency derived as the file's rules say
+pub fn parse(text: &str) -> Result<Vec<Prototype>, String> {
+    let tileset: TilesetToml =
+        toml::from_str(text).map_err(|e| format!("Invalid tileset TOML: {}", e))?;
+    if tileset.modules.is_empty() {
+        return Err("No modules found".into());
+    }
+
+    let mut ids = HashSet::new();
+    if let Some(module) = tileset.modules.iter().find(|m| !ids.insert(m.id.as_str())) {
+        return Err(format!("Module id '{}' is used more than once", module.id));
+    }
+
+    let mut protos: Vec<Prototype> = tileset.modules.into_iter().map(Prototype::from).collect();
+    if tileset.rules.expand_rotations {
+        protos = RotationExpansion {
+            dedupe_symmetric: tileset.rules.dedupe_symmetric,
+        }
+        .apply(&protos);
+    }
+    SocketRules {
+        allow_same_mesh: tileset.rules.allow_same_mesh,
+    }
+    .apply(&mut protos);
+    Ok(protos)
+}
+
+// Writes every prototype as its own module, without expanding rotations. Only prototypes whose
+//  valid_neighbours follow from their sockets can be written, since the lists themselves aren't.
+pub fn serialize(protos: &[Prototype]) -> Result<String, String> {
+    let mut derived = protos.to_vec();
+    SocketRules::default().apply(&mut derived);
+    for (proto, derived) in protos.iter().zip(derived.iter()) {
+        if proto.valid_neighbors.is_empty() {
+            continue;
+        }
+        let listed: Vec<HashSet<&String>> = proto
+            .valid_neighbors
+            .iter()
+            .map(|neighbors| neighbors.iter().collect())
+            .collect();
+        let fitting: Vec<HashSet<&String>> = derived
+            .valid_neighbors
+            .iter()
+            .map(|neighbors| neighbors.iter().collect())
+            .collect();
+        if listed != fitting {
+            return Err(format!(
+                "The valid_neighbours of '{}' don't match its sockets, so it can't be written as \
+                 TOML",
+                proto.id
+            ));
+        }
+    }
+
+    let tileset = TilesetToml {
+        rules: Rules::default(),
+        modules: protos.iter().map(Module::from).collect(),
+    };
+    toml::to_string(&tileset).map_err(|e| format!("Failed to serialize prototypes: {}", e))
+}
+
+impl From<Module> for Prototype {
+    fn from(module: Module) -> Self {
+        Self {
+            id: module.id,
+            mesh_name: module.mesh,
+            mesh_rotation: module.rotation,
+            pos_x: module.pos_x,
+            neg_x: module.neg_x,
+            pos_y: module.pos_y,
+            neg_y: module.neg_y,
+            pos_z: module.pos_z,
+            neg_z: module.neg_z,
+            constrain_to: module.constrain_to,
+            constrain_from: module.constrain_from,
+            weight: module.weight,
+            no_id: module.no_id,
+            no_id_sym: module.no_id_sym,
+            tags: module.tags,
+            valid_neighbors: vec![],
+        }
+    }
+}
+
+impl From<&Prototype> for Module {
+    fn from(proto: &Prototype) -> Self {
+        Self {
+            id: proto.id.clone(),
+            mesh: proto.mesh_name.clone(),
+            rotation: proto.mesh_rotation,
+            weight: proto.weight,
+            tags: proto.tags.clone(),
+            pos_x: proto.pos_x.clone(),
+            neg_x: proto.neg_x.clone(),
+            pos_y: proto.pos_y.clone(),
+            neg_y: proto.neg_y.clone(),
+            pos_z: proto.pos_z.clone(),
+            neg_z: proto.neg_z.clone(),
+            constrain_to: proto.constrain_to.clone(),
+            constrain_from: proto.constrain_from.clone(),
+            no_id: proto.no_id,
+            no_id_sym: proto.no_id_sym,
+        }
+    }
+}
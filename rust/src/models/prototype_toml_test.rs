@@ -0,0 +1,122 @@
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::models::prototype::Prototype;
+    use crate::models::prototype_toml::{parse, serialize};
+
+    const TILESET: &str = r#"
+        [rules]
+        expand_rotations = true
+
+        [[module]]
+        id = "line"
+        mesh = "line_mesh"
+        weight = 3
+        tags = ["road"]
+        posX = "1s"
+        negX = "1s"
+        posY = "0s"
+        negY = "0s"
+        posZ = "-1"
+        negZ = "-1"
+
+        [[module]]
+        id = "ground"
+        mesh = "ground_mesh"
+        posX = "0s"
+        negX = "0s"
+        posY = "0s"
+        negY = "0s"
+        posZ = "-1"
+        negZ = "-1"
+    "#;
+
+    fn neighbors(proto: &Prototype, direction: usize) -> HashSet<&str> {
+        proto.valid_neighbors[direction]
+            .iter()
+            .map(|id| id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        let protos = parse(TILESET).unwrap();
+
+        // The line has two distinct rotations, and the ground only one
+        let ids: Vec<&str> = protos.iter().map(|p| p.id.as_str()).collect();
+        assert_eq!(vec!["line", "line_1", "ground"], ids);
+
+        let line = &protos[0];
+        assert_eq!(3.0, line.weight);
+        assert_eq!(vec!["road".to_string()], line.tags);
+        assert_eq!(1.0, protos[2].weight);
+        assert_eq!(1, protos[1].mesh_rotation);
+
+        // Lines only continue into other lines, which the default rules don't allow, and ground
+        //  meets their sides
+        assert!(neighbors(line, 0).is_empty());
+        assert_eq!(HashSet::from(["ground"]), neighbors(line, 1));
+        assert_eq!(HashSet::from(["line_1"]), neighbors(&protos[2], 0));
+    }
+
+    #[test]
+    fn test_rules() {
+        let tileset = TILESET.replace(
+            "expand_rotations = true",
+            "allow_same_mesh = true\nexpand_rotations = false",
+        );
+        let protos = parse(&tileset).unwrap();
+        assert_eq!(2, protos.len());
+        assert_eq!(HashSet::from(["line"]), neighbors(&protos[0], 0));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let duplicate = TILESET.replace("id = \"ground\"", "id = \"line\"");
+        let tests = [
+            ("not toml", "[[module"),
+            ("no modules", "[rules]\nexpand_rotations = true"),
+            (
+                "missing socket",
+                "[[module]]\nid = \"a\"\nmesh = \"a\"\nposX = \"0s\"",
+            ),
+            ("unknown field", &TILESET.replace("tags", "tag")),
+            ("duplicate id", &duplicate),
+        ];
+
+        for (name, toml) in tests {
+            assert!(parse(toml).is_err(), "Test Failed: {}", name);
+        }
+    }
+
+    #[test]
+    fn test_exported_round_trip() {
+        let exported =
+            Prototype::parse(include_str!("../../../godot/prototype_data.json")).unwrap();
+        let toml = serialize(&exported).unwrap();
+        assert!(!toml.contains("valid_neighbours"));
+        assert!(toml.len() * 10 < include_str!("../../../godot/prototype_data.json").len());
+
+        let parsed = parse(&toml).unwrap();
+        assert_eq!(exported.len(), parsed.len());
+        for (expected, actual) in exported.iter().zip(parsed.iter()) {
+            assert_eq!(expected.id, actual.id);
+            assert_eq!(expected.mesh_rotation, actual.mesh_rotation);
+            assert_eq!(expected.weight, actual.weight);
+            for direction in 0..6 {
+                let expected: HashSet<&String> =
+                    expected.valid_neighbors[direction].iter().collect();
+                let actual: HashSet<&String> = actual.valid_neighbors[direction].iter().collect();
+                assert_eq!(expected, actual, "Test Failed: {}", expected.len());
+            }
+        }
+    }
+
+    #[test]
+    fn test_serialize_rejects_hand_edited_neighbours() {
+        let mut protos = parse(TILESET).unwrap();
+        protos[0].valid_neighbors[0].push("line".into());
+        assert!(serialize(&protos).unwrap_err().contains("'line'"));
+    }
+}
@@ -0,0 +1,28 @@
+// What happens once a chunk has failed every retry
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Escalation {
+    // Keep the chunk as it is and report it as failed
+    Accept,
+    // Put the chunk back and collapse it again as smaller chunks, one per octant
+    Shrink,
+    // Put the chunk back and collapse it again grown by the chunk overlap on every side, so more of
+    //  the finished chunks around it are reset too
+    WidenReset,
+}
+
+// How a chunk that hits a contradiction is handled. The chunk's cells are put back as they were
+//  before it started, the rng is reseeded and the chunk is tried again, up to `max_retries` times.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub escalation: Escalation,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            escalation: Escalation::Accept,
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use godot::builtin::Vector3i;
+
+use super::prototype::Prototype;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TagRule {
+    // Only prototypes with at least one of the tags. Untagged prototypes, like the empty one, need
+    //  a tag of their own to be allowed.
+    Only(Vec<String>),
+    // No prototypes with any of the tags
+    Exclude(Vec<String>),
+}
+
+// Limits which prototypes may appear in a box of cells by their tags, e.g. "only walkable in the
+//  spawn area", or "no water above y = 2" with a box starting at y = 3 that runs past the map top
+#[derive(Debug, Clone, PartialEq)]
+pub struct TagConstraint {
+    pub position: Vector3i,
+    pub size: Vector3i,
+    pub rule: TagRule,
+}
+
+impl TagConstraint {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.size.x <= 0 || self.size.y <= 0 || self.size.z <= 0 {
+            return Err(format!("Region size must be positive, got {}", self.size));
+        }
+        let tags = match &self.rule {
+            TagRule::Only(tags) | TagRule::Exclude(tags) => tags,
+        };
+        if tags.is_empty() {
+            return Err("Tag constraint has no tags".into());
+        }
+        Ok(())
+    }
+
+    pub fn contains(&self, position: Vector3i) -> bool {
+        let end = self.position + self.size;
+        (self.position.x..end.x).contains(&position.x)
+            && (self.position.y..end.y).contains(&position.y)
+            && (self.position.z..end.z).contains(&position.z)
+    }
+
+    pub fn allows(&self, proto: &Prototype) -> bool {
+        match &self.rule {
+            TagRule::Only(tags) => tags.iter().any(|tag| proto.has_tag(tag)),
+            TagRule::Exclude(tags) => !tags.iter().any(|tag| proto.has_tag(tag)),
+        }
+    }
+}
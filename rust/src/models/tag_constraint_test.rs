@@ -0,0 +1,93 @@
+#[cfg(test)]
+mod tests {
+    use godot::builtin::Vector3i;
+
+    use crate::models::prototype::Prototype;
+    use crate::models::prototype_selector::PrototypeSelector;
+    use crate::models::tag_constraint::{TagConstraint, TagRule};
+
+    const TILES: &str = r#"{
+        "grass": {
+            "mesh_name": "grass_mesh", "mesh_rotation": 0,
+            "posX": "0s", "negX": "0s", "posY": "0s", "negY": "0s", "posZ": "-1", "negZ": "-1",
+            "constrain_to": "", "constrain_from": "", "weight": 1.0,
+            "tags": ["walkable", "ground"]
+        },
+        "pond": {
+            "mesh_name": "pond_mesh", "mesh_rotation": 0,
+            "posX": "0s", "negX": "0s", "posY": "0s", "negY": "0s", "posZ": "-1", "negZ": "-1",
+            "constrain_to": "", "constrain_from": "", "weight": 1.0,
+            "tags": ["water", "ground"]
+        },
+        "empty": {
+            "mesh_name": "-1", "mesh_rotation": 0,
+            "posX": "-1", "negX": "-1", "posY": "-1", "negY": "-1", "posZ": "-1", "negZ": "-1",
+            "constrain_to": "", "constrain_from": "", "weight": 1.0
+        }
+    }"#;
+
+    fn tiles() -> (Prototype, Prototype, Prototype) {
+        let protos = Prototype::parse(TILES).unwrap();
+        let find = |id: &str| protos.iter().find(|p| p.id == id).unwrap().clone();
+        (find("grass"), find("pond"), find("empty"))
+    }
+
+    fn constraint(rule: TagRule) -> TagConstraint {
+        TagConstraint {
+            position: Vector3i::new(0, 3, 0),
+            size: Vector3i::new(4, 100, 4),
+            rule,
+        }
+    }
+
+    #[test]
+    fn test_parse_tags() {
+        let (grass, _, empty) = tiles();
+        assert_eq!(vec!["walkable", "ground"], grass.tags);
+        assert!(grass.has_tag("walkable"));
+        assert!(!grass.has_tag("water"));
+        assert!(empty.tags.is_empty());
+    }
+
+    #[test]
+    fn test_tag_selector() {
+        let (grass, pond, empty) = tiles();
+        let ground = PrototypeSelector::Tag("ground".into());
+        assert!(ground.matches(&grass));
+        assert!(ground.matches(&pond));
+        assert!(!ground.matches(&empty));
+    }
+
+    #[test]
+    fn test_rules() {
+        let (grass, pond, empty) = tiles();
+
+        // "Only walkable": untagged prototypes are left out too
+        let only = constraint(TagRule::Only(vec!["walkable".into()]));
+        assert!(only.allows(&grass));
+        assert!(!only.allows(&pond));
+        assert!(!only.allows(&empty));
+
+        // "No water above y = 2"
+        let exclude = constraint(TagRule::Exclude(vec!["water".into()]));
+        assert!(exclude.allows(&grass));
+        assert!(!exclude.allows(&pond));
+        assert!(exclude.allows(&empty));
+        assert!(exclude.contains(Vector3i::new(0, 3, 0)));
+        assert!(exclude.contains(Vector3i::new(3, 50, 3)));
+        assert!(!exclude.contains(Vector3i::new(0, 2, 0)));
+        assert!(!exclude.contains(Vector3i::new(4, 3, 0)));
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut empty_region = constraint(TagRule::Only(vec!["walkable".into()]));
+        empty_region.size = Vector3i::new(4, 0, 4);
+
+        assert!(constraint(TagRule::Only(vec!["walkable".into()]))
+            .validate()
+            .is_ok());
+        assert!(constraint(TagRule::Exclude(vec![])).validate().is_err());
+        assert!(empty_region.validate().is_err());
+    }
+}
@@ -0,0 +1,27 @@
+use crate::models::prototype::Prototype;
+
+// A prototype with no sockets, allowed next to the given ids. The lists are in valid_neighbors
+//  order: +x, +y, -x, -y, +z, -z.
+pub fn proto(id: &str, weight: f32, valid_neighbors: [&[&str]; 6]) -> Prototype {
+    Prototype {
+        id: id.into(),
+        mesh_name: id.into(),
+        mesh_rotation: 0,
+        pos_x: "".into(),
+        neg_x: "".into(),
+        pos_y: "".into(),
+        neg_y: "".into(),
+        pos_z: "".into(),
+        neg_z: "".into(),
+        constrain_to: "".into(),
+        constrain_from: "".into(),
+        weight,
+        no_id: 0,
+        no_id_sym: 0,
+        tags: vec![],
+        valid_neighbors: valid_neighbors
+            .iter()
+            .map(|ids| ids.iter().map(|id| id.to_string()).collect())
+            .collect(),
+    }
+}
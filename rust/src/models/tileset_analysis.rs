@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+
+use godot::builtin::Vector3i;
+
+use super::prototype::{Prototype, DIRECTION_NAMES, DIRECTION_OFFSETS, HORIZONTAL};
+
+// A prototype that arc consistency removes from a grid with no edges and no constraints. It can
+//  only ever appear against the edge of the map, and anywhere else it leads to a contradiction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EliminatedPrototype {
+    pub id: String,
+    // The side left with no possible neighbours once it was removed
+    pub side: &'static str,
+}
+
+// A block of prototypes that satisfies every rule when repeated forever, including across its own
+//  edges, which proves the tileset can tile infinitely
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeriodicTile {
+    pub period: Vector3i,
+    // Every position in the block, from the origin up to (but not including) period
+    pub cells: Vec<(Vector3i, String)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SolvabilityReport {
+    pub eliminated: Vec<EliminatedPrototype>,
+    // The smallest tile found, if any
+    pub periodic_tile: Option<PeriodicTile>,
+    // False if the search ran out of steps before trying every period, in which case a missing
+    //  tile doesn't prove there isn't one
+    pub search_complete: bool,
+}
+
+// Works out whether a tileset can fill an unbounded map, before it's used for one.
+// Only the rule table is used: valid_neighbors of prototypes that don't exist are ignored.
+#[derive(Debug, Clone, Copy)]
+pub struct SolvabilityAnalysis {
+    // Whether the map is unbounded vertically too. Tilesets built for a single layer, with only
+    //  the empty prototype above and below, should be analysed without it.
+    pub vertical: bool,
+    // The largest period along each axis tried when looking for a periodic tile
+    pub max_period: i32,
+    // How many prototypes the search may try placing, across every period
+    pub max_steps: usize,
+}
+
+impl Default for SolvabilityAnalysis {
+    fn default() -> Self {
+        Self {
+            vertical: true,
+            max_period: 4,
+            max_steps: 100_000,
+        }
+    }
+}
+
+impl SolvabilityAnalysis {
+    pub fn run(&self, protos: &[Prototype]) -> SolvabilityReport {
+        let rules = RuleTable::new(protos, self.directions());
+        let (remaining, eliminated) = rules.eliminate();
+
+        let mut steps = self.max_steps;
+        let mut periodic_tile = None;
+        if !remaining.is_empty() {
+            for period in self.periods() {
+                let torus = Torus::new(period, &rules);
+                if let Some(assignment) = torus.solve(&remaining, &mut steps) {
+                    periodic_tile = Some(PeriodicTile {
+                        period,
+                        cells: assignment
+                            .into_iter()
+                            .enumerate()
+                            .map(|(i, proto)| (torus.position(i), protos[proto].id.clone()))
+                            .collect(),
+                    });
+                    break;
+                }
+                if steps == 0 {
+                    break;
+                }
+            }
+        }
+
+        SolvabilityReport {
+            eliminated: eliminated
+                .into_iter()
+                .map(|(proto, direction)| EliminatedPrototype {
+                    id: protos[proto].id.clone(),
+                    side: DIRECTION_NAMES[direction],
+                })
+                .collect(),
+            search_complete: periodic_tile.is_some() || steps > 0,
+            periodic_tile,
+        }
+    }
+
+    fn directions(&self) -> Vec<usize> {
+        if self.vertical {
+            (0..6).collect()
+        } else {
+            HORIZONTAL.to_vec()
+        }
+    }
+
+    // Every period up to max_period, smallest tiles first
+    fn periods(&self) -> Vec<Vector3i> {
+        let max_vertical = if self.vertical { self.max_period } else { 1 };
+        let mut periods = vec![];
+        for x in 1..=self.max_period {
+            for y in 1..=max_vertical {
+                for z in 1..=self.max_period {
+                    periods.push(Vector3i::new(x, y, z));
+                }
+            }
+        }
+        periods.sort_by_key(|p| (p.x * p.y * p.z, p.x, p.y, p.z));
+        periods
+    }
+}
+
+// A set of prototype indices
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Domain(Vec<u64>);
+
+impl Domain {
+    fn empty(len: usize) -> Self {
+        Self(vec![0; len.div_ceil(64)])
+    }
+
+    fn single(len: usize, index: usize) -> Self {
+        let mut domain = Self::empty(len);
+        domain.insert(index);
+        domain
+    }
+
+    fn insert(&mut self, index: usize) {
+        self.0[index / 64] |= 1 << (index % 64);
+    }
+
+    fn remove(&mut self, index: usize) {
+        self.0[index / 64] &= !(1 << (index % 64));
+    }
+
+    fn len(&self) -> usize {
+        self.0.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.iter().all(|word| *word == 0)
+    }
+
+    fn intersects(&self, other: &Domain) -> bool {
+        self.0.iter().zip(other.0.iter()).any(|(a, b)| a & b != 0)
+    }
+
+    fn union_with(&mut self, other: &Domain) {
+        for (a, b) in self.0.iter_mut().zip(other.0.iter()) {
+            *a |= b;
+        }
+    }
+
+    fn intersect_with(&mut self, other: &Domain) {
+        for (a, b) in self.0.iter_mut().zip(other.0.iter()) {
+            *a &= b;
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.iter().enumerate().flat_map(|(i, word)| {
+            (0..64)
+                .filter(move |bit| word & (1 << bit) != 0)
+                .map(move |bit| i * 64 + bit)
+        })
+    }
+}
+
+struct RuleTable {
+    len: usize,
+    directions: Vec<usize>,
+    // allowed[proto][direction] is every prototype that may sit on that side of it
+    allowed: Vec<Vec<Domain>>,
+}
+
+impl RuleTable {
+    fn new(protos: &[Prototype], directions: Vec<usize>) -> Self {
+        let len = protos.len();
+        let indices: HashMap<&str, usize> = protos
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (p.id.as_str(), i))
+            .collect();
+        let allowed = protos
+            .iter()
+            .map(|proto| {
+                (0..6)
+                    .map(|direction| {
+                        let mut domain = Domain::empty(len);
+                        let neighbors = proto.valid_neighbors.get(direction);
+                        for id in neighbors.into_iter().flatten() {
+                            if let Some(index) = indices.get(id.as_str()) {
+                                domain.insert(*index);
+                            }
+                        }
+                        domain
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self {
+            len,
+            directions,
+            allowed,
+        }
+    }
+
+    // Arc consistency on a grid where every cell starts with every prototype. Every cell stays the
+    //  same, so this only has to look at one. Returns what's left, and what was removed along
+    //  with the direction that ran out of neighbours, in the order they were removed.
+    fn eliminate(&self) -> (Domain, Vec<(usize, usize)>) {
+        let mut remaining = Domain::empty(self.len);
+        (0..self.len).for_each(|proto| remaining.insert(proto));
+
+        let mut eliminated = vec![];
+        loop {
+            let removed: Vec<(usize, usize)> = remaining
+                .iter()
+                .filter_map(|proto| {
+                    self.directions
+                        .iter()
+                        .find(|&&direction| !self.allowed[proto][direction].intersects(&remaining))
+                        .map(|&direction| (proto, direction))
+                })
+                .collect();
+            if removed.is_empty() {
+                return (remaining, eliminated);
+            }
+            for (proto, _) in removed.iter() {
+                remaining.remove(*proto);
+            }
+            eliminated.extend(removed);
+        }
+    }
+
+    // Every prototype allowed on the given side of any prototype in `domain`
+    fn support(&self, domain: &Domain, direction: usize) -> Domain {
+        let mut support = Domain::empty(self.len);
+        for proto in domain.iter() {
+            support.union_with(&self.allowed[proto][direction]);
+        }
+        support
+    }
+}
+
+// A block of cells whose edges wrap around to the opposite side
+struct Torus<'a> {
+    period: Vector3i,
+    rules: &'a RuleTable,
+    // neighbors[cell][direction], for the directions the rules use
+    neighbors: Vec<Vec<(usize, usize)>>,
+}
+
+impl<'a> Torus<'a> {
+    fn new(period: Vector3i, rules: &'a RuleTable) -> Self {
+        let count = (period.x * period.y * period.z) as usize;
+        let mut torus = Self {
+            period,
+            rules,
+            neighbors: vec![],
+        };
+        torus.neighbors = (0..count)
+            .map(|cell| {
+                let position = torus.position(cell);
+                rules
+                    .directions
+                    .iter()
+                    .map(|&direction| {
+                        let neighbor = position + DIRECTION_OFFSETS[direction];
+                        (direction, torus.index(neighbor))
+                    })
+                    .collect()
+            })
+            .collect();
+        torus
+    }
+
+    fn position(&self, index: usize) -> Vector3i {
+        let index = index as i32;
+        Vector3i::new(
+            index % self.period.x,
+            index / self.period.x % self.period.y,
+            index / (self.period.x * self.period.y),
+        )
+    }
+
+    fn index(&self, position: Vector3i) -> usize {
+        let x = position.x.rem_euclid(self.period.x);
+        let y = position.y.rem_euclid(self.period.y);
+        let z = position.z.rem_euclid(self.period.z);
+        (x + self.period.x * (y + self.period.y * z)) as usize
+    }
+
+    // Backtracking search, placing a prototype in the cell with the fewest options each step.
+    //  Returns the prototype for each cell.
+    fn solve(&self, remaining: &Domain, steps: &mut usize) -> Option<Vec<usize>> {
+        let mut domains = vec![remaining.clone(); self.neighbors.len()];
+        let all = (0..domains.len()).collect();
+        if !self.propagate(&mut domains, all) {
+            return None;
+        }
+        self.search(domains, steps)
+    }
+
+    fn search(&self, domains: Vec<Domain>, steps: &mut usize) -> Option<Vec<usize>> {
+        let undecided = (0..domains.len())
+            .filter(|&cell| domains[cell].len() > 1)
+            .min_by_key(|&cell| domains[cell].len());
+        let Some(cell) = undecided else {
+            return Some(domains.iter().filter_map(|d| d.iter().next()).collect());
+        };
+
+        for proto in domains[cell].iter() {
+            if *steps == 0 {
+                return None;
+            }
+            *steps -= 1;
+
+            let mut next = domains.clone();
+            next[cell] = Domain::single(self.rules.len, proto);
+            if self.propagate(&mut next, vec![cell]) {
+                if let Some(solution) = self.search(next, steps) {
+                    return Some(solution);
+                }
+            }
+        }
+        None
+    }
+
+    // Narrows each cell's neighbours to what it supports, until nothing changes. Returns false on
+    //  a contradiction.
+    fn propagate(&self, domains: &mut [Domain], mut queue: Vec<usize>) -> bool {
+        while let Some(cell) = queue.pop() {
+            for &(direction, neighbor) in self.neighbors[cell].iter() {
+                let support = self.rules.support(&domains[cell], direction);
+                let mut narrowed = domains[neighbor].clone();
+                narrowed.intersect_with(&support);
+                if narrowed == domains[neighbor] {
+                    continue;
+                }
+                if narrowed.is_empty() {
+                    return false;
+                }
+                domains[neighbor] = narrowed;
+                queue.push(neighbor);
+            }
+        }
+        true
+    }
+}
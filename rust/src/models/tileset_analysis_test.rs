@@ -0,0 +1,115 @@
+#[cfg(test)]
+mod tests {
+    use godot::builtin::Vector3i;
+
+    use std::collections::HashMap;
+
+    use crate::models::prototype::{Prototype, DIRECTION_OFFSETS, HORIZONTAL};
+    use crate::models::test_fixtures;
+    use crate::models::tileset_analysis::{PeriodicTile, SolvabilityAnalysis};
+
+    fn proto(id: &str, horizontal: [&[&str]; 4]) -> Prototype {
+        let [px, py, nx, ny] = horizontal;
+        test_fixtures::proto(id, 1.0, [px, py, nx, ny, &[], &[]])
+    }
+
+    // Every cell's neighbours, wrapping around the tile's edges, are allowed by its rules
+    fn assert_tiles(protos: &[Prototype], tile: &PeriodicTile, directions: &[usize]) {
+        let by_id: HashMap<&str, &Prototype> = protos.iter().map(|p| (p.id.as_str(), p)).collect();
+        let cells: HashMap<Vector3i, &str> = tile
+            .cells
+            .iter()
+            .map(|(position, id)| (*position, id.as_str()))
+            .collect();
+        let period = tile.period;
+        assert_eq!((period.x * period.y * period.z) as usize, cells.len());
+
+        for (position, id) in cells.iter() {
+            for &direction in directions {
+                let next = *position + DIRECTION_OFFSETS[direction];
+                let wrapped = Vector3i::new(
+                    next.x.rem_euclid(period.x),
+                    next.y.rem_euclid(period.y),
+                    next.z.rem_euclid(period.z),
+                );
+                let neighbor = cells[&wrapped].to_string();
+                assert!(
+                    by_id[id].valid_neighbors[direction].contains(&neighbor),
+                    "{} at {:?} doesn't allow {} on side {}",
+                    id,
+                    position,
+                    neighbor,
+                    direction
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_eliminates_and_finds_tile() {
+        let protos = vec![
+            proto("a", [&["b"], &["b"], &["b"], &["b"]]),
+            proto("b", [&["a"], &["a"], &["a"], &["a"]]),
+            // Only fits next to "e", which has nothing on +x, so both go
+            proto("d", [&["e"], &["d"], &["d"], &["d"]]),
+            proto("e", [&[], &["e"], &["d"], &["e"]]),
+        ];
+        let analysis = SolvabilityAnalysis {
+            vertical: false,
+            ..Default::default()
+        };
+        let report = analysis.run(&protos);
+
+        let eliminated: Vec<(&str, &str)> = report
+            .eliminated
+            .iter()
+            .map(|e| (e.id.as_str(), e.side))
+            .collect();
+        assert_eq!(vec![("e", "+x"), ("d", "+x")], eliminated);
+
+        // A checkerboard, the smallest period where "a" and "b" alternate along both axes
+        let tile = report.periodic_tile.unwrap();
+        assert_eq!(Vector3i::new(2, 1, 2), tile.period);
+        assert_tiles(&protos, &tile, &HORIZONTAL);
+        assert!(report.search_complete);
+    }
+
+    #[test]
+    fn test_no_tile_when_everything_is_eliminated() {
+        let protos = vec![proto("a", [&["a"], &["a"], &["a"], &["a"]])];
+        let report = SolvabilityAnalysis::default().run(&protos);
+
+        let eliminated: Vec<(&str, &str)> = report
+            .eliminated
+            .iter()
+            .map(|e| (e.id.as_str(), e.side))
+            .collect();
+        assert_eq!(vec![("a", "+z")], eliminated);
+        assert_eq!(None, report.periodic_tile);
+        assert!(report.search_complete);
+    }
+
+    #[test]
+    fn test_shipped_tileset() {
+        let protos = Prototype::parse(include_str!("../../../godot/prototype_data.json")).unwrap();
+
+        // Every module has only the empty prototype above and below it, and that has nothing to
+        //  either side, so nothing survives in three dimensions
+        let report = SolvabilityAnalysis::default().run(&protos);
+        assert_eq!(protos.len(), report.eliminated.len());
+        assert_eq!(None, report.periodic_tile);
+
+        // In a single layer, only the empty prototype and the edge pieces are eliminated
+        let analysis = SolvabilityAnalysis {
+            vertical: false,
+            ..Default::default()
+        };
+        let report = analysis.run(&protos);
+        let mut eliminated: Vec<&str> = report.eliminated.iter().map(|e| e.id.as_str()).collect();
+        eliminated.sort();
+        assert_eq!(vec!["p-1", "p280", "p281", "p282", "p283"], eliminated);
+
+        let tile = report.periodic_tile.unwrap();
+        assert_tiles(&protos, &tile, &HORIZONTAL);
+    }
+}
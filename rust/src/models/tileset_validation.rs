@@ -0,0 +1,169 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use super::prototype::{
+    Prototype, SocketRules, DIRECTION_NAMES, N_X, N_Y, N_Z, OPPOSITE, P_X, P_Y, P_Z,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    // The tileset will generate, but probably not the way its author meant
+    Warning,
+    // The solver will misbehave: propagation depends on the direction it runs in, or refers to
+    //  prototypes that don't exist
+    Error,
+}
+
+impl Severity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TilesetIssue {
+    pub severity: Severity,
+    pub prototype: String,
+    pub message: String,
+}
+
+impl fmt::Display for TilesetIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {}: {}",
+            self.severity.name(),
+            self.prototype,
+            self.message
+        )
+    }
+}
+
+// Checks a loaded tileset for mistakes the solver won't notice by itself. Issues are grouped by
+//  prototype, in the order the prototypes were given.
+pub fn validate(protos: &[Prototype]) -> Vec<TilesetIssue> {
+    let by_id: HashMap<&str, &Prototype> = protos.iter().map(|p| (p.id.as_str(), p)).collect();
+    let referenced: HashSet<&str> = protos
+        .iter()
+        .flat_map(|p| p.valid_neighbors.iter().flatten())
+        .map(|id| id.as_str())
+        .collect();
+
+    let mut derived = protos.to_vec();
+    SocketRules::default().apply(&mut derived);
+
+    let mut issues = vec![];
+    for (proto, derived) in protos.iter().zip(derived.iter()) {
+        let mut report = |severity, message: String| {
+            issues.push(TilesetIssue {
+                severity,
+                prototype: proto.id.clone(),
+                message,
+            })
+        };
+
+        if proto.weight <= 0.0 || !proto.weight.is_finite() {
+            report(
+                Severity::Error,
+                format!("weight must be positive, got {}", proto.weight),
+            );
+        }
+        if proto.valid_neighbors.len() != 6 {
+            report(
+                Severity::Error,
+                format!(
+                    "has {} valid_neighbours lists, expected 6",
+                    proto.valid_neighbors.len()
+                ),
+            );
+        }
+
+        for (direction, neighbors) in proto.valid_neighbors.iter().enumerate().take(6) {
+            let name = DIRECTION_NAMES[direction];
+            for id in neighbors {
+                let Some(other) = by_id.get(id.as_str()) else {
+                    report(
+                        Severity::Error,
+                        format!("unknown prototype '{}' in valid_neighbours on {}", id, name),
+                    );
+                    continue;
+                };
+                let allowed_back = other
+                    .valid_neighbors
+                    .get(OPPOSITE[direction])
+                    .is_some_and(|back| back.contains(&proto.id));
+                if !allowed_back {
+                    report(
+                        Severity::Error,
+                        format!(
+                            "allows '{}' on {}, but '{}' doesn't allow it on {}",
+                            id, name, id, DIRECTION_NAMES[OPPOSITE[direction]]
+                        ),
+                    );
+                }
+            }
+        }
+
+        if !referenced.contains(proto.id.as_str()) {
+            report(
+                Severity::Warning,
+                "no prototype allows it as a neighbour, so it can never appear".into(),
+            );
+        }
+        for (axis, (positive, negative)) in
+            [("x", (P_X, N_X)), ("y", (P_Y, N_Y)), ("z", (P_Z, N_Z))]
+        {
+            let is_empty = |direction: usize| {
+                proto
+                    .valid_neighbors
+                    .get(direction)
+                    .is_none_or(|neighbors| neighbors.is_empty())
+            };
+            if is_empty(positive) && is_empty(negative) {
+                report(
+                    Severity::Warning,
+                    format!(
+                        "has no valid neighbours on either side along {}, so it can never appear \
+                         in a map more than one cell across",
+                        axis
+                    ),
+                );
+            }
+        }
+
+        // Tilesets without sockets have nothing to compare against
+        let has_sockets = [&proto.pos_x, &proto.pos_y, &proto.neg_x, &proto.neg_y]
+            .iter()
+            .any(|socket| !socket.is_empty());
+        if !has_sockets {
+            continue;
+        }
+        let (mut directions, mut unexpected, mut missing) = (vec![], 0, 0);
+        for (direction, neighbors) in proto.valid_neighbors.iter().enumerate().take(6) {
+            let listed: HashSet<&String> = neighbors.iter().collect();
+            let fitting: HashSet<&String> = derived.valid_neighbors[direction].iter().collect();
+            if listed != fitting {
+                directions.push(DIRECTION_NAMES[direction]);
+                unexpected += listed.difference(&fitting).count();
+                missing += fitting.difference(&listed).count();
+            }
+        }
+        if !directions.is_empty() {
+            report(
+                Severity::Warning,
+                format!(
+                    "valid_neighbours doesn't match its sockets on {} ({} listed that don't fit, \
+                     {} that fit aren't listed)",
+                    directions.join(", "),
+                    unexpected,
+                    missing
+                ),
+            );
+        }
+    }
+
+    issues
+}
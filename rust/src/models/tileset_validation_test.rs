@@ -0,0 +1,107 @@
+#[cfg(test)]
+mod tests {
+    use crate::models::prototype::Prototype;
+    use crate::models::test_fixtures::proto;
+    use crate::models::tileset_validation::{validate, Severity};
+
+    #[test]
+    fn test_validate_shipped_tileset() {
+        let protos = Prototype::parse(include_str!("../../../godot/prototype_data.json")).unwrap();
+        let issues = validate(&protos);
+
+        // The empty prototype only ever sits above or below other modules
+        let summary: Vec<(Severity, &str)> = issues
+            .iter()
+            .map(|issue| (issue.severity, issue.prototype.as_str()))
+            .collect();
+        assert_eq!(
+            vec![(Severity::Warning, "p-1"), (Severity::Warning, "p-1")],
+            summary,
+            "{:?}",
+            issues
+        );
+    }
+
+    #[test]
+    fn test_validate_consistent_tileset() {
+        let protos = vec![
+            proto("a", 1.0, [&["b"], &["a"], &["b"], &["a"], &["a"], &["a"]]),
+            proto("b", 1.0, [&["a"], &["b"], &["a"], &["b"], &["b"], &["b"]]),
+        ];
+        assert_eq!(0, validate(&protos).len(), "{:?}", validate(&protos));
+    }
+
+    #[test]
+    fn test_validate_errors() {
+        let protos = vec![
+            // Allows "b" on +x, but "b" doesn't allow "a" on -x
+            proto("a", 1.0, [&["b"], &["a"], &["b"], &["a"], &[], &[]]),
+            proto("b", 0.0, [&["a"], &["b", "c"], &[], &["b"], &[], &[]]),
+        ];
+        let issues = validate(&protos);
+        let errors: Vec<(&str, &str)> = issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .map(|issue| (issue.prototype.as_str(), issue.message.as_str()))
+            .collect();
+
+        assert_eq!(
+            vec![
+                ("a", "allows 'b' on +x, but 'b' doesn't allow it on -x"),
+                ("b", "weight must be positive, got 0"),
+                ("b", "unknown prototype 'c' in valid_neighbours on +y"),
+            ],
+            errors
+        );
+    }
+
+    #[test]
+    fn test_validate_warnings() {
+        let mut protos = vec![
+            proto("a", 1.0, [&["a"], &["a"], &["a"], &["a"], &["a"], &["a"]]),
+            // Never listed, and can't sit next to anything
+            proto("lonely", 1.0, [&[], &[], &[], &[], &[], &[]]),
+        ];
+        // Nothing has a socket that fits this one
+        protos[0].pos_x = "1".into();
+
+        let issues = validate(&protos);
+        let messages: Vec<(&str, &str)> = issues
+            .iter()
+            .map(|issue| (issue.prototype.as_str(), issue.message.as_str()))
+            .collect();
+
+        assert!(issues
+            .iter()
+            .all(|issue| issue.severity == Severity::Warning));
+        assert_eq!(
+            vec![
+                (
+                    "a",
+                    "valid_neighbours doesn't match its sockets on +x, +y, -x, -y, +z, -z \
+                     (6 listed that don't fit, 0 that fit aren't listed)"
+                ),
+                (
+                    "lonely",
+                    "no prototype allows it as a neighbour, so it can never appear"
+                ),
+                (
+                    "lonely",
+                    "has no valid neighbours on either side along x, so it can never appear in a \
+                     map more than one cell across"
+                ),
+                (
+                    "lonely",
+                    "has no valid neighbours on either side along y, so it can never appear in a \
+                     map more than one cell across"
+                ),
+                (
+                    "lonely",
+                    "has no valid neighbours on either side along z, so it can never appear in a \
+                     map more than one cell across"
+                ),
+            ],
+            messages
+        );
+    }
+}
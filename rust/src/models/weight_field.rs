@@ -0,0 +1,161 @@
+use godot::builtin::Vector3i;
+
+use super::{prototype::Prototype, prototype_selector::PrototypeSelector};
+
+// Where a layer's multipliers come from
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldSource {
+    // Multipliers on a grid stretched over the whole map, indexed x first, then z, then y. A grid
+    //  one cell tall applies to every height, so a 2D map or image covers the map from above.
+    Grid {
+        size: Vector3i,
+        values: Vec<f32>,
+    },
+    // Smooth value noise over x and z, with features about `scale` cells across, mapped onto
+    //  low..high. Noise is sampled in world positions, so it doesn't move if the map is resized.
+    Noise {
+        seed: u64,
+        scale: f32,
+        low: f32,
+        high: f32,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeightLayer {
+    pub selector: PrototypeSelector,
+    pub source: FieldSource,
+}
+
+impl WeightLayer {
+    pub fn validate(&self) -> Result<(), String> {
+        match &self.source {
+            FieldSource::Grid { size, values } => {
+                if size.x <= 0 || size.y <= 0 || size.z <= 0 {
+                    return Err(format!("Weight map size must be positive, got {}", size));
+                }
+                let expected = (size.x as usize)
+                    .checked_mul(size.y as usize)
+                    .and_then(|area| area.checked_mul(size.z as usize))
+                    .ok_or(format!("Weight map size {} is too large", size))?;
+                if values.len() != expected {
+                    return Err(format!(
+                        "Weight map of size {} needs {} values, got {}",
+                        size,
+                        expected,
+                        values.len()
+                    ));
+                }
+                if values.iter().any(|v| *v <= 0.0 || !v.is_finite()) {
+                    return Err("Weight map values must be positive".into());
+                }
+            }
+            FieldSource::Noise {
+                scale, low, high, ..
+            } => {
+                if *scale <= 0.0 {
+                    return Err(format!("Noise scale must be positive, got {}", scale));
+                }
+                if *low <= 0.0 || *high <= 0.0 {
+                    return Err(format!(
+                        "Noise range must be positive, got {} to {}",
+                        low, high
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // The multiplier at a position in a map with the given origin and size
+    pub fn sample(&self, position: Vector3i, origin: Vector3i, map_size: Vector3i) -> f32 {
+        match &self.source {
+            FieldSource::Grid { size, values } => {
+                let local = position - origin;
+                let scale = |v: i32, grid: i32, map: i32| {
+                    (v as i64 * grid as i64 / map.max(1) as i64).clamp(0, grid as i64 - 1) as i32
+                };
+                let x = scale(local.x, size.x, map_size.x);
+                let y = scale(local.y, size.y, map_size.y);
+                let z = scale(local.z, size.z, map_size.z);
+                let (x, y, z) = (x as usize, y as usize, z as usize);
+                values[x + size.x as usize * (z + size.z as usize * y)]
+            }
+            FieldSource::Noise {
+                seed,
+                scale,
+                low,
+                high,
+            } => {
+                let noise =
+                    value_noise(*seed, position.x as f32 / scale, position.z as f32 / scale);
+                low + (high - low) * noise
+            }
+        }
+    }
+}
+
+// Scales prototype weights by position, so one tileset can favour different modules in different
+//  parts of the map. Where layers overlap their multipliers are multiplied together.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WeightField {
+    pub layers: Vec<WeightLayer>,
+}
+
+impl WeightField {
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    // Every layer's multiplier at a position, for looking up many prototypes at once
+    pub fn sample(
+        &self,
+        position: Vector3i,
+        origin: Vector3i,
+        map_size: Vector3i,
+    ) -> FieldSample<'_> {
+        FieldSample {
+            multipliers: self
+                .layers
+                .iter()
+                .map(|layer| (&layer.selector, layer.sample(position, origin, map_size)))
+                .collect(),
+        }
+    }
+}
+
+pub struct FieldSample<'a> {
+    multipliers: Vec<(&'a PrototypeSelector, f32)>,
+}
+
+impl FieldSample<'_> {
+    pub fn weight(&self, proto: &Prototype) -> f32 {
+        self.multipliers
+            .iter()
+            .filter(|(selector, _)| selector.matches(proto))
+            .fold(proto.weight, |weight, (_, multiplier)| weight * multiplier)
+    }
+}
+
+// Smoothly interpolated random values on the integer lattice, between 0 and 1
+fn value_noise(seed: u64, x: f32, z: f32) -> f32 {
+    let (x0, z0) = (x.floor(), z.floor());
+    let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+    let (tx, tz) = (smooth(x - x0), smooth(z - z0));
+    let corner = |dx: i64, dz: i64| lattice_value(seed, x0 as i64 + dx, z0 as i64 + dz);
+
+    let near = corner(0, 0) + (corner(1, 0) - corner(0, 0)) * tx;
+    let far = corner(0, 1) + (corner(1, 1) - corner(0, 1)) * tx;
+    near + (far - near) * tz
+}
+
+fn lattice_value(seed: u64, x: i64, z: i64) -> f32 {
+    // SplitMix64 finalizer over the seed and both coordinates
+    let mut h = seed
+        ^ (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (z as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+    h = (h ^ (h >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    h ^= h >> 31;
+    (h >> 40) as f32 / (1u64 << 24) as f32
+}
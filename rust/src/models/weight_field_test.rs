@@ -0,0 +1,161 @@
+#[cfg(test)]
+mod tests {
+    use godot::builtin::Vector3i;
+
+    use crate::models::prototype::Prototype;
+    use crate::models::prototype_selector::PrototypeSelector;
+    use crate::models::weight_field::{FieldSource, WeightField, WeightLayer};
+
+    const TREES: &str = r#"{
+        "tree": {
+            "mesh_name": "tree_mesh", "mesh_rotation": 0,
+            "posX": "0s", "negX": "0s", "posY": "0s", "negY": "0s", "posZ": "-1", "negZ": "-1",
+            "constrain_to": "", "constrain_from": "", "weight": 2.0
+        },
+        "rock": {
+            "mesh_name": "rock_mesh", "mesh_rotation": 0,
+            "posX": "0s", "negX": "0s", "posY": "0s", "negY": "0s", "posZ": "-1", "negZ": "-1",
+            "constrain_to": "", "constrain_from": "", "weight": 1.0
+        }
+    }"#;
+
+    fn grid(name: &str, size: Vector3i, values: Vec<f32>) -> WeightLayer {
+        WeightLayer {
+            selector: PrototypeSelector::Mesh(name.into()),
+            source: FieldSource::Grid { size, values },
+        }
+    }
+
+    #[test]
+    fn test_grid_is_stretched_over_the_map() {
+        // A 2x1x2 grid over a 4x3x4 map at (10, 0, 10): each value covers a 2x2 column
+        let layer = grid(
+            "tree_mesh",
+            Vector3i::new(2, 1, 2),
+            vec![1.0, 2.0, 3.0, 4.0],
+        );
+        let origin = Vector3i::new(10, 0, 10);
+        let size = Vector3i::new(4, 3, 4);
+
+        let tests = [
+            (Vector3i::new(10, 0, 10), 1.0),
+            (Vector3i::new(11, 2, 11), 1.0),
+            (Vector3i::new(12, 0, 10), 2.0),
+            (Vector3i::new(10, 1, 12), 3.0),
+            (Vector3i::new(13, 2, 13), 4.0),
+        ];
+        for (position, expected) in tests {
+            assert_eq!(
+                expected,
+                layer.sample(position, origin, size),
+                "Test Failed: {}",
+                position
+            );
+        }
+    }
+
+    #[test]
+    fn test_layers_multiply_for_matching_prototypes() {
+        let protos = Prototype::parse(TREES).unwrap();
+        let tree = protos.iter().find(|p| p.id == "tree").unwrap();
+        let rock = protos.iter().find(|p| p.id == "rock").unwrap();
+
+        let one = Vector3i::new(1, 1, 1);
+        let field = WeightField {
+            layers: vec![
+                grid("tree_mesh", one, vec![3.0]),
+                grid("tree_mesh", one, vec![0.5]),
+                grid("rock_mesh", one, vec![4.0]),
+            ],
+        };
+        let sample = field.sample(Vector3i::ZERO, Vector3i::ZERO, Vector3i::new(8, 1, 8));
+        assert_eq!(3.0, sample.weight(tree));
+        assert_eq!(4.0, sample.weight(rock));
+
+        let empty = WeightField::default();
+        let sample = empty.sample(Vector3i::ZERO, Vector3i::ZERO, Vector3i::new(8, 1, 8));
+        assert_eq!(2.0, sample.weight(tree));
+    }
+
+    #[test]
+    fn test_noise_stays_in_range() {
+        let layer = WeightLayer {
+            selector: PrototypeSelector::Id("tree".into()),
+            source: FieldSource::Noise {
+                seed: 7,
+                scale: 4.0,
+                low: 0.5,
+                high: 3.0,
+            },
+        };
+        let (origin, size) = (Vector3i::ZERO, Vector3i::new(32, 1, 32));
+
+        let mut samples = vec![];
+        for x in -16..16 {
+            for z in -16..16 {
+                let value = layer.sample(Vector3i::new(x, 0, z), origin, size);
+                assert!((0.5..=3.0).contains(&value), "{} at {}, {}", value, x, z);
+                // The same everywhere up a column, and the same every time
+                assert_eq!(value, layer.sample(Vector3i::new(x, 5, z), origin, size));
+                samples.push(value);
+            }
+        }
+
+        // Neighbouring cells are close, since features span several cells
+        let steps = samples
+            .windows(2)
+            .filter(|w| (w[0] - w[1]).abs() > 1.0)
+            .count();
+        assert!(steps < samples.len() / 8, "{} large steps", steps);
+    }
+
+    #[test]
+    fn test_validate() {
+        let one = Vector3i::new(1, 1, 1);
+        let tests = [
+            ("ok", grid("tree_mesh", one, vec![1.0]), true),
+            (
+                "zero size",
+                grid("tree_mesh", Vector3i::ZERO, vec![]),
+                false,
+            ),
+            (
+                "too few values",
+                grid("tree_mesh", Vector3i::new(2, 1, 1), vec![1.0]),
+                false,
+            ),
+            (
+                "more values than an i32 can count",
+                grid("tree_mesh", Vector3i::new(65536, 65536, 1), vec![]),
+                false,
+            ),
+            (
+                "more values than a usize can count",
+                grid(
+                    "tree_mesh",
+                    Vector3i::new(i32::MAX, i32::MAX, i32::MAX),
+                    vec![],
+                ),
+                false,
+            ),
+            ("zero value", grid("tree_mesh", one, vec![0.0]), false),
+            (
+                "negative noise",
+                WeightLayer {
+                    selector: PrototypeSelector::Id("tree".into()),
+                    source: FieldSource::Noise {
+                        seed: 0,
+                        scale: 1.0,
+                        low: -1.0,
+                        high: 1.0,
+                    },
+                },
+                false,
+            ),
+        ];
+
+        for (name, layer, valid) in tests {
+            assert_eq!(valid, layer.validate().is_ok(), "Test Failed: {}", name);
+        }
+    }
+}
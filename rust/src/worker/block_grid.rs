@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+
+use godot::prelude::*;
+
+use super::{grid::DIRECTIONS, storage::CellStorage};
+
+// Edge length of a block, in cells
+const BLOCK_SIZE: i32 = 8;
+const BLOCK_VOLUME: usize = (BLOCK_SIZE * BLOCK_SIZE * BLOCK_SIZE) as usize;
+
+// Storage for a box of cells at any signed coordinates. Cells live in fixed-size blocks, keyed by
+//  the block's coordinate (position divided by BLOCK_SIZE, rounding down). Every cell in the box is
+//  stored, but the blocks along its edges may be partly empty.
+// A cell's index is its block's slot times BLOCK_VOLUME plus its offset within the block.
+pub struct BlockGrid<T> {
+    block_slots: HashMap<Vector3i, usize>,
+    blocks: Vec<Block<T>>,
+    len: usize,
+}
+
+struct Block<T> {
+    coordinate: Vector3i,
+    cells: Vec<Option<T>>,
+}
+
+impl<T> BlockGrid<T> {
+    pub fn new<F: FnMut(Vector3i) -> T>(origin: Vector3i, size: Vector3i, mut f: F) -> Self {
+        let mut grid = Self {
+            block_slots: HashMap::new(),
+            blocks: vec![],
+            len: 0,
+        };
+        for y in origin.y..origin.y + size.y {
+            for x in origin.x..origin.x + size.x {
+                for z in origin.z..origin.z + size.z {
+                    let position = Vector3i { x, y, z };
+                    grid.insert(position, f(position));
+                }
+            }
+        }
+
+        grid
+    }
+
+    // Stores a cell, allocating its block if needed
+    fn insert(&mut self, position: Vector3i, value: T) {
+        let (coordinate, offset) = split(position);
+        let slot = match self.block_slots.get(&coordinate) {
+            Some(slot) => *slot,
+            None => {
+                self.blocks.push(Block {
+                    coordinate,
+                    cells: (0..BLOCK_VOLUME).map(|_| None).collect(),
+                });
+                self.block_slots.insert(coordinate, self.blocks.len() - 1);
+                self.blocks.len() - 1
+            }
+        };
+
+        let cell = &mut self.blocks[slot].cells[offset];
+        if cell.is_none() {
+            self.len += 1;
+        }
+        *cell = Some(value);
+    }
+}
+
+impl<T> CellStorage<T> for BlockGrid<T> {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn index_of(&self, position: Vector3i) -> Option<usize> {
+        let (coordinate, offset) = split(position);
+        let slot = *self.block_slots.get(&coordinate)?;
+        self.blocks[slot].cells[offset].as_ref()?;
+        Some(slot * BLOCK_VOLUME + offset)
+    }
+
+    fn position_of(&self, index: usize) -> Vector3i {
+        let block = &self.blocks[index / BLOCK_VOLUME];
+        let offset = (index % BLOCK_VOLUME) as i32;
+        let local = Vector3i {
+            x: (offset / BLOCK_SIZE) % BLOCK_SIZE,
+            y: offset / (BLOCK_SIZE * BLOCK_SIZE),
+            z: offset % BLOCK_SIZE,
+        };
+        block.coordinate * BLOCK_SIZE + local
+    }
+
+    fn at(&self, index: usize) -> &T {
+        self.blocks[index / BLOCK_VOLUME].cells[index % BLOCK_VOLUME]
+            .as_ref()
+            .expect("no cell at block index")
+    }
+
+    fn at_mut(&mut self, index: usize) -> &mut T {
+        self.blocks[index / BLOCK_VOLUME].cells[index % BLOCK_VOLUME]
+            .as_mut()
+            .expect("no cell at block index")
+    }
+
+    fn neighbors(&self, index: usize) -> [Option<usize>; 6] {
+        let position = self.position_of(index);
+        DIRECTIONS.map(|direction| self.index_of(position + direction))
+    }
+
+    fn indices_in(
+        &self,
+        position: Vector3i,
+        size: Vector3i,
+    ) -> Box<dyn Iterator<Item = usize> + '_> {
+        let end = position + size;
+        Box::new((position.y..end.y).flat_map(move |y| {
+            (position.x..end.x).flat_map(move |x| {
+                (position.z..end.z).filter_map(move |z| self.index_of(Vector3i { x, y, z }))
+            })
+        }))
+    }
+
+    fn indices(&self) -> Box<dyn Iterator<Item = usize> + '_> {
+        Box::new(self.blocks.iter().enumerate().flat_map(|(slot, block)| {
+            block
+                .cells
+                .iter()
+                .enumerate()
+                .filter(|(_, cell)| cell.is_some())
+                .map(move |(offset, _)| slot * BLOCK_VOLUME + offset)
+        }))
+    }
+}
+
+// Block coordinate and offset within that block, laid out [y][x][z] like Grid
+fn split(position: Vector3i) -> (Vector3i, usize) {
+    let coordinate = Vector3i {
+        x: position.x.div_euclid(BLOCK_SIZE),
+        y: position.y.div_euclid(BLOCK_SIZE),
+        z: position.z.div_euclid(BLOCK_SIZE),
+    };
+    let local = position - coordinate * BLOCK_SIZE;
+    let offset = (local.y * BLOCK_SIZE + local.x) * BLOCK_SIZE + local.z;
+    (coordinate, offset as usize)
+}
@@ -0,0 +1,58 @@
+#[cfg(test)]
+mod tests {
+    use godot::builtin::Vector3i;
+
+    use crate::worker::{block_grid::BlockGrid, grid::Grid, storage::CellStorage};
+
+    fn v(x: i32, y: i32, z: i32) -> Vector3i {
+        Vector3i { x, y, z }
+    }
+
+    #[test]
+    fn test_negative_coordinates() {
+        let grid = BlockGrid::new(v(-10, -1, -20), v(13, 2, 25), |position| position);
+        assert_eq!(13 * 2 * 25, grid.len());
+
+        for index in grid.indices() {
+            assert_eq!(*grid.at(index), grid.position_of(index));
+            assert_eq!(Some(index), grid.index_of(grid.position_of(index)));
+        }
+
+        assert_eq!(Some(&v(-10, -1, -20)), grid.get(v(-10, -1, -20)));
+        assert_eq!(Some(&v(2, 0, 4)), grid.get(v(2, 0, 4)));
+        assert_eq!(None, grid.get(v(-11, 0, 0)));
+        assert_eq!(None, grid.get(v(3, 0, 0)));
+        assert_eq!(None, grid.get(v(0, -2, 0)));
+    }
+
+    #[test]
+    fn test_matches_dense() {
+        let origin = v(-5, 0, -9);
+        let size = v(11, 2, 17);
+        let dense = Grid::new(origin, size, |position| position);
+        let blocks = BlockGrid::new(origin, size, |position| position);
+
+        let region = (v(-7, 0, -3), v(6, 3, 20));
+        let from_dense: Vec<Vector3i> = dense
+            .indices_in(region.0, region.1)
+            .map(|i| *dense.at(i))
+            .collect();
+        let from_blocks: Vec<Vector3i> = blocks
+            .indices_in(region.0, region.1)
+            .map(|i| *blocks.at(i))
+            .collect();
+        assert_eq!(from_dense, from_blocks);
+
+        for position in [v(-5, 0, -9), v(0, 1, 0), v(5, 1, 7), v(2, 0, -1)] {
+            let neighbors = |storage: &dyn CellStorage<Vector3i>| -> Vec<Option<Vector3i>> {
+                let index = storage.index_of(position).unwrap();
+                storage
+                    .neighbors(index)
+                    .iter()
+                    .map(|n| n.map(|i| *storage.at(i)))
+                    .collect()
+            };
+            assert_eq!(neighbors(&dense), neighbors(&blocks), "{}", position);
+        }
+    }
+}
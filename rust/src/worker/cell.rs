@@ -3,22 +3,19 @@ use rand::prelude::*;
 
 use crate::models::{driver_update::CellChange, prototype::Prototype};
 
+// A cell doesn't know its own position; that's implied by where it's stored in the map's Grid
 pub struct Cell {
-    pub position: Vector3i,
     pub possibilities: Vec<Prototype>,
 }
 
 impl Cell {
-    pub fn new(position: Vector3i, possibilities: Vec<Prototype>) -> Self {
-        Self {
-            position,
-            possibilities,
-        }
+    pub fn new(possibilities: Vec<Prototype>) -> Self {
+        Self { possibilities }
     }
 
-    pub fn changes_from(&self, other: &CellChange) -> Option<CellChange> {
+    pub fn changes_from(&self, position: Vector3i, other: &CellChange) -> Option<CellChange> {
         let mut new_protos = vec![];
-        let direction = other.position - self.position;
+        let direction = other.position - position;
 
         for proto in self.possibilities.iter() {
             if proto.compatible_with_any(&other.new_protos, direction) {
@@ -28,7 +25,7 @@ impl Cell {
 
         if new_protos.len() != self.possibilities.len() {
             return Some(CellChange {
-                position: self.position,
+                position,
                 new_protos,
             });
         }
@@ -36,14 +33,18 @@ impl Cell {
         None
     }
 
-    pub fn change(&mut self, prototypes: &Vec<Prototype>) -> Option<CellChange> {
+    pub fn change(
+        &mut self,
+        position: Vector3i,
+        prototypes: &Vec<Prototype>,
+    ) -> Option<CellChange> {
         let old_length = self.possibilities.len();
 
         self.possibilities = prototypes.clone();
 
         if self.possibilities.len() != old_length {
             return Some(CellChange {
-                position: self.position,
+                position,
                 new_protos: self.possibilities.clone(),
             });
         }
@@ -51,24 +52,28 @@ impl Cell {
         None
     }
 
-    pub fn collapse(&mut self, prototype: Option<Prototype>) -> Option<CellChange> {
+    // `weight` gives each possibility's weight at this cell's position
+    pub fn collapse<R: Rng>(
+        &mut self,
+        position: Vector3i,
+        prototype: Option<Prototype>,
+        weight: impl Fn(&Prototype) -> f32,
+        rng: &mut R,
+    ) -> Option<CellChange> {
         let old_length = self.possibilities.len();
 
         if let Some(proto) = prototype {
             self.possibilities = vec![proto];
-        } else if let Some(selected) = self.choose_weighted() {
+        } else if let Some(selected) = self.choose_weighted(weight, rng) {
             self.possibilities = vec![selected];
         } else {
-            godot_print!(
-                "Tried to collapse but already overcollapsed! {}",
-                self.position
-            );
+            log_print!("Tried to collapse but already overcollapsed! {}", position);
             self.possibilities = vec![];
         }
 
         if self.possibilities.len() != old_length {
             return Some(CellChange {
-                position: self.position,
+                position,
                 new_protos: self.possibilities.clone(), // TODO - can we avoid cloning here?
             });
         }
@@ -80,21 +85,40 @@ impl Cell {
         self.possibilities.len()
     }
 
+    // Shannon entropy of the possibilities, given each one's weight
+    pub fn weighted_entropy(&self, weight: impl Fn(&Prototype) -> f32) -> f32 {
+        let weights: Vec<f32> = self.possibilities.iter().map(weight).collect();
+        let sum: f32 = weights.iter().sum();
+        if sum <= 0.0 {
+            return 0.0;
+        }
+        let sum_of_logs: f32 = weights
+            .iter()
+            .filter(|w| **w > 0.0)
+            .map(|w| w * w.ln())
+            .sum();
+        sum.ln() - sum_of_logs / sum
+    }
+
     fn _is_collapsed(&self) -> bool {
         self.possibilities.len() <= 1
     }
 
-    fn choose_weighted(&mut self) -> Option<Prototype> {
-        let sum_of_weights = self.possibilities.iter().fold(0.0, |l, p| l + p.weight);
-        let mut selected_weight = rand::thread_rng().gen_range(0.0..sum_of_weights);
+    fn choose_weighted<R: Rng>(
+        &mut self,
+        weight: impl Fn(&Prototype) -> f32,
+        rng: &mut R,
+    ) -> Option<Prototype> {
+        let sum_of_weights = self.possibilities.iter().fold(0.0, |l, p| l + weight(p));
+        let mut selected_weight = rng.gen_range(0.0..sum_of_weights);
         for prototype in self.possibilities.iter() {
-            selected_weight -= prototype.weight;
+            selected_weight -= weight(prototype);
             if selected_weight <= 0.0 {
                 return Some(prototype.clone());
             }
         }
 
-        godot_error!(
+        log_error!(
             "selected a weight greater than sum_of_weights! sow: {}",
             sum_of_weights
         );
@@ -1,9 +1,8 @@
 use godot::prelude::*;
-use rand::Rng;
 
 use crate::models::driver_update::CellChange;
 
-use super::map::Map;
+use super::{grid::DIRECTIONS, map::Map};
 
 #[derive(Clone, Copy)]
 pub struct Chunk {
@@ -16,6 +15,14 @@ impl Chunk {
         Self { position, size }
     }
 
+    pub fn position(&self) -> Vector3i {
+        self.position
+    }
+
+    pub fn size(&self) -> Vector3i {
+        self.size
+    }
+
     // used in tests maybe?
     pub fn _get_all_cells(&self) -> Vec<Vector3i> {
         self.map_filter_cells(|position| Some(position))
@@ -63,11 +70,11 @@ impl Chunk {
     // Used in conjunction with get_neighbors to pull in changes from neighboring chunks
     pub fn propagate_from(&self, cells: Vec<Vector3i>, map: &mut Map) -> Vec<CellChange> {
         let mut changes = vec![];
-        for cell in cells {
-            if let Some(cell) = map.get_cell(cell) {
+        for position in cells {
+            if let Some(cell) = map.get_cell(position) {
                 changes.append(&mut self.propagate(
                     &CellChange {
-                        position: cell.position,
+                        position,
                         new_protos: cell.possibilities.clone(),
                     },
                     map,
@@ -79,21 +86,15 @@ impl Chunk {
     }
 
     pub fn collapse_next(&self, map: &mut Map) -> Option<Vec<CellChange>> {
-        let cell_position = self.select_lowest_entropy(map)?;
-        let cell = map.get_cell_mut(cell_position)?;
-        let change = cell.collapse(None)?;
+        let cell_position = map.lowest_entropy_cell()?;
+        let change = map.collapse_cell(cell_position)?;
         Some(self.propagate(&change, map))
     }
 
-    pub fn propagate_all(&self, map: &mut Map) -> Vec<CellChange> {
-        self.change_each_cell(map, |position, map| {
-            let cell = map.get_cell(position)?;
-            let cell_change = CellChange {
-                position,
-                new_protos: cell.possibilities.clone(),
-            };
-            Some(self.propagate(&cell_change, map))
-        })
+    // Propagate from every cell the map has marked dirty since the last call
+    pub fn propagate_dirty(&self, map: &mut Map) -> Vec<CellChange> {
+        let dirty = map.take_dirty();
+        self.propagate_from(dirty, map)
     }
 
     // Propagate a given cell change into other cells within this chunk
@@ -101,70 +102,97 @@ impl Chunk {
         let mut changes: Vec<CellChange> = vec![];
         changes.push(change.clone());
 
-        for neighbor_position in self.get_cell_neighbors(change.position, 1).iter() {
-            if let Some(neighbor_cell) = map.get_cell_mut(*neighbor_position) {
-                if let Some(neighbor_change) = neighbor_cell.changes_from(change) {
-                    if neighbor_change.new_protos.len() == 0 {
-                        godot_print!(
-                            "overcollapsed {} while propagating {:?}",
-                            neighbor_position,
-                            change
-                        );
-                        continue;
-                    }
+        let Some(index) = map.cells().index_of(change.position) else {
+            return changes;
+        };
+
+        for neighbor_index in map.cells().neighbors(index).into_iter().flatten() {
+            let neighbor_position = map.cells().position_of(neighbor_index);
+            if !self.contains(neighbor_position) {
+                continue;
+            }
 
-                    neighbor_cell.change(&neighbor_change.new_protos);
-                    changes.append(&mut self.propagate(&neighbor_change.clone(), map));
+            let neighbor_cell = map.cells().at(neighbor_index);
+            if let Some(neighbor_change) = neighbor_cell.changes_from(neighbor_position, change) {
+                if neighbor_change.new_protos.len() == 0 {
+                    log_print!(
+                        "overcollapsed {} while propagating {:?}",
+                        neighbor_position,
+                        change
+                    );
+                    map.record_contradiction(neighbor_position);
+                    continue;
                 }
+
+                map.set_possibilities(neighbor_position, &neighbor_change.new_protos);
+                changes.append(&mut self.propagate(&neighbor_change.clone(), map));
             }
         }
 
         changes
     }
 
-    // Select the "lowest entropy" cell and collapse it.
-    // In reality, there are some rules in place to maintain stability that mean that this is often
-    //  not the true lowest-entropy cell.
-    fn select_lowest_entropy(&self, map: &mut Map) -> Option<Vector3i> {
-        let mut lowest_entropy = usize::MAX;
-        let mut lowest_entropy_cells = vec![];
+    pub fn center(&self) -> Vector3i {
+        self.position + self.size / 2
+    }
 
-        let start = self.position;
+    // The part of this chunk inside `bounds`. Has a zero or negative size if they don't overlap.
+    pub fn clipped_to(&self, bounds: &Chunk) -> Chunk {
         let end = self.position + self.size;
-        for x in start.x..end.x {
-            for y in self.position.y..end.y {
-                for z in self.position.z..end.z {
-                    let position = Vector3i { x, y, z };
-                    let cell = map.get_cell(position);
-                    if let Some(cell) = cell {
-                        let entropy = cell.entropy();
-                        if entropy <= 1 || entropy > lowest_entropy {
-                            continue;
-                        }
-
-                        if entropy < lowest_entropy {
-                            lowest_entropy = entropy;
-                            lowest_entropy_cells = vec![position];
-                        } else if entropy == lowest_entropy {
-                            lowest_entropy_cells.push(position);
-                        } else {
-                            // unreachable!()
-                        }
-                    }
+        let bounds_end = bounds.position + bounds.size;
+        let start = Vector3i {
+            x: self.position.x.max(bounds.position.x),
+            y: self.position.y.max(bounds.position.y),
+            z: self.position.z.max(bounds.position.z),
+        };
+        let end = Vector3i {
+            x: end.x.min(bounds_end.x),
+            y: end.y.min(bounds_end.y),
+            z: end.z.min(bounds_end.z),
+        };
+        Chunk::new(start, end - start)
+    }
+
+    // This chunk extended by `by` cells on every side
+    pub fn grown(&self, by: Vector3i) -> Chunk {
+        Chunk::new(self.position - by, self.size + by * 2)
+    }
+
+    // Splits the chunk in half along every axis longer than one cell, in y, then x, then z order.
+    // A single cell can't be split, so it comes back as is.
+    pub fn octants(&self) -> Vec<Chunk> {
+        let halves = |start: i32, length: i32| {
+            if length > 1 {
+                vec![
+                    (start, length / 2),
+                    (start + length / 2, length - length / 2),
+                ]
+            } else {
+                vec![(start, length)]
+            }
+        };
+
+        let mut octants = vec![];
+        for (y, height) in halves(self.position.y, self.size.y) {
+            for (x, width) in halves(self.position.x, self.size.x) {
+                for (z, depth) in halves(self.position.z, self.size.z) {
+                    octants.push(Chunk::new(
+                        Vector3i { x, y, z },
+                        Vector3i {
+                            x: width,
+                            y: height,
+                            z: depth,
+                        },
+                    ));
                 }
             }
         }
 
-        if lowest_entropy_cells.len() >= 1 {
-            let selected_weight = rand::thread_rng().gen_range(0..lowest_entropy_cells.len());
-            return Some(lowest_entropy_cells[selected_weight]);
-        }
-
-        None
+        octants
     }
 
     // Returns true iff the given position is located within this chunk
-    fn contains(&self, position: Vector3i) -> bool {
+    pub fn contains(&self, position: Vector3i) -> bool {
         let start = self.position;
         let end = self.position + self.size;
 
@@ -181,7 +209,7 @@ impl Chunk {
     // Diagonal cells are not returned. Cells that are not within this chunk are not returned.
     fn get_cell_neighbors(self, cell_position: Vector3i, n: i32) -> Vec<Vector3i> {
         let mut neighbors = vec![];
-        for direction in DIRECTIONS {
+        for direction in DIRECTIONS.iter() {
             for i in 1..=n {
                 let neighbor_position = cell_position + (*direction * i);
                 if self.contains(neighbor_position) {
@@ -195,28 +223,6 @@ impl Chunk {
 
     // ITERATING UTILS
 
-    fn change_each_cell<F: Fn(Vector3i, &mut Map) -> Option<Vec<CellChange>>>(
-        &self,
-        map: &mut Map,
-        f: F,
-    ) -> Vec<CellChange> {
-        let mut changes = vec![];
-
-        let start = self.position;
-        let end = self.position + self.size;
-        for x in start.x..end.x {
-            for y in start.y..end.y {
-                for z in start.z..end.z {
-                    if let Some(mut changes_applied) = f(Vector3i { x, y, z }, map) {
-                        changes.append(&mut changes_applied)
-                    }
-                }
-            }
-        }
-
-        changes
-    }
-
     fn map_filter_cells<F: Fn(Vector3i) -> Option<Vector3i>>(&self, f: F) -> Vec<Vector3i> {
         let mut cells = vec![];
 
@@ -235,12 +241,3 @@ impl Chunk {
         cells
     }
 }
-
-const DIRECTIONS: &'static [Vector3i] = &[
-    Vector3i::UP,
-    Vector3i::DOWN,
-    Vector3i::RIGHT,
-    Vector3i::LEFT,
-    Vector3i::FORWARD,
-    Vector3i::BACK,
-];
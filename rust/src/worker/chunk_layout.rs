@@ -0,0 +1,99 @@
+use godot::prelude::*;
+
+use super::chunk::Chunk;
+
+// Tiles a map with chunks. Chunks start every `chunk_size - overlap` cells along each axis, and the
+//  last chunk on each axis is clipped to the map bounds, so every cell is in at least one chunk and
+//  no chunk extends past the edge of the map.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChunkLayout {
+    map_size: Vector3i,
+    chunk_size: Vector3i,
+    overlap: Vector3i,
+}
+
+impl ChunkLayout {
+    pub fn new(
+        map_size: Vector3i,
+        chunk_size: Vector3i,
+        overlap: Vector3i,
+    ) -> Result<Self, String> {
+        if map_size.x <= 0 || map_size.y <= 0 || map_size.z <= 0 {
+            return Err(format!("Map size must be positive, got {}", map_size));
+        }
+        if chunk_size.x <= 0 || chunk_size.y <= 0 || chunk_size.z <= 0 {
+            return Err(format!("Chunk size must be positive, got {}", chunk_size));
+        }
+        if overlap.x < 0 || overlap.y < 0 || overlap.z < 0 {
+            return Err(format!("Chunk overlap can't be negative, got {}", overlap));
+        }
+
+        // Overlap only matters along axes that need more than one chunk
+        for (axis, map, chunk, overlap) in [
+            ("x", map_size.x, chunk_size.x, overlap.x),
+            ("y", map_size.y, chunk_size.y, overlap.y),
+            ("z", map_size.z, chunk_size.z, overlap.z),
+        ] {
+            if chunk < map && overlap >= chunk {
+                return Err(format!(
+                    "Chunk overlap along {} ({}) must be smaller than the chunk size ({})",
+                    axis, overlap, chunk
+                ));
+            }
+        }
+
+        Ok(Self {
+            map_size,
+            chunk_size,
+            overlap,
+        })
+    }
+
+    // Distance between the starts of neighbouring chunks
+    pub fn stride(&self) -> Vector3i {
+        self.chunk_size - self.overlap
+    }
+
+    // In y, then x, then z order
+    pub fn chunks(&self) -> Vec<Chunk> {
+        let stride = self.stride();
+        let xs = axis_spans(self.map_size.x, self.chunk_size.x, stride.x);
+        let ys = axis_spans(self.map_size.y, self.chunk_size.y, stride.y);
+        let zs = axis_spans(self.map_size.z, self.chunk_size.z, stride.z);
+
+        let mut chunks = vec![];
+        for (y, height) in ys.iter() {
+            for (x, width) in xs.iter() {
+                for (z, depth) in zs.iter() {
+                    chunks.push(Chunk::new(
+                        Vector3i {
+                            x: *x,
+                            y: *y,
+                            z: *z,
+                        },
+                        Vector3i {
+                            x: *width,
+                            y: *height,
+                            z: *depth,
+                        },
+                    ));
+                }
+            }
+        }
+
+        chunks
+    }
+}
+
+// Start and length of each chunk along one axis
+fn axis_spans(map: i32, chunk: i32, stride: i32) -> Vec<(i32, i32)> {
+    let mut spans = vec![];
+    let mut start = 0;
+    loop {
+        spans.push((start, chunk.min(map - start)));
+        if start + chunk >= map {
+            return spans;
+        }
+        start += stride;
+    }
+}
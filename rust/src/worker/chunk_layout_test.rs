@@ -0,0 +1,103 @@
+#[cfg(test)]
+mod tests {
+    use godot::builtin::Vector3i;
+
+    use crate::worker::chunk_layout::ChunkLayout;
+
+    fn v(x: i32, y: i32, z: i32) -> Vector3i {
+        Vector3i { x, y, z }
+    }
+
+    #[test]
+    fn test_covers_every_cell_within_bounds() {
+        let cases = vec![
+            (v(15, 1, 15), v(9, 1, 9), v(2, 2, 2)),
+            (v(10, 1, 10), v(4, 1, 4), v(1, 0, 1)),
+            (v(7, 3, 11), v(3, 2, 5), v(1, 1, 0)),
+            (v(20, 1, 3), v(6, 1, 6), v(3, 0, 5)),
+            (v(1, 1, 1), v(9, 9, 9), v(2, 2, 2)),
+        ];
+
+        for (map_size, chunk_size, overlap) in cases {
+            let name = format!("{} / {} / {}", map_size, chunk_size, overlap);
+            let chunks = ChunkLayout::new(map_size, chunk_size, overlap)
+                .unwrap()
+                .chunks();
+
+            for chunk in chunks.iter() {
+                let start = chunk.position();
+                let end = chunk.position() + chunk.size();
+                assert!(start.x >= 0 && start.y >= 0 && start.z >= 0, "{}", name);
+                assert!(
+                    end.x <= map_size.x && end.y <= map_size.y && end.z <= map_size.z,
+                    "{}: chunk at {} of size {} is out of bounds",
+                    name,
+                    chunk.position(),
+                    chunk.size()
+                );
+            }
+
+            for x in 0..map_size.x {
+                for y in 0..map_size.y {
+                    for z in 0..map_size.z {
+                        let position = v(x, y, z);
+                        assert!(
+                            chunks.iter().any(|c| c.contains(position)),
+                            "{}: {} isn't in any chunk",
+                            name,
+                            position
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_clips_last_chunk_on_each_axis() {
+        let layout = ChunkLayout::new(v(10, 1, 7), v(4, 1, 7), v(1, 0, 0)).unwrap();
+        let spans: Vec<(i32, i32)> = layout
+            .chunks()
+            .iter()
+            .map(|c| (c.position().x, c.size().x))
+            .collect();
+
+        assert_eq!(vec![(0, 4), (3, 4), (6, 4)], spans);
+    }
+
+    #[test]
+    fn test_overlap_per_axis() {
+        let layout = ChunkLayout::new(v(8, 1, 8), v(4, 1, 4), v(0, 0, 2)).unwrap();
+        assert_eq!(v(4, 1, 2), layout.stride());
+
+        let chunks = layout.chunks();
+        let xs: Vec<i32> = chunks.iter().map(|c| c.position().x).collect();
+        let zs: Vec<i32> = chunks.iter().map(|c| c.position().z).collect();
+        assert_eq!(vec![0, 0, 0, 4, 4, 4], xs);
+        assert_eq!(vec![0, 2, 4, 0, 2, 4], zs);
+    }
+
+    #[test]
+    fn test_rejects_invalid_configurations() {
+        let cases = vec![
+            ("empty map", v(0, 1, 5), v(3, 1, 3), v(1, 0, 1)),
+            ("empty chunk", v(5, 1, 5), v(3, 0, 3), v(1, 0, 1)),
+            ("negative overlap", v(5, 1, 5), v(3, 1, 3), v(-1, 0, 1)),
+            ("overlap fills chunk", v(9, 1, 9), v(3, 1, 3), v(3, 0, 1)),
+        ];
+
+        for (name, map_size, chunk_size, overlap) in cases {
+            assert!(
+                ChunkLayout::new(map_size, chunk_size, overlap).is_err(),
+                "Test Failed: {}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_ignores_overlap_when_one_chunk_spans_the_axis() {
+        let layout = ChunkLayout::new(v(9, 1, 5), v(3, 1, 5), v(1, 0, 5)).unwrap();
+        assert!(layout.chunks().iter().all(|c| c.size().z == 5));
+    }
+}
@@ -168,4 +168,56 @@ mod tests {
 
         runner(tests);
     }
+
+    #[test]
+    fn test_octants() {
+        let v = |x, y, z| Vector3i { x, y, z };
+        let bounds = |chunks: Vec<Chunk>| -> Vec<(Vector3i, Vector3i)> {
+            chunks.iter().map(|c| (c.position(), c.size())).collect()
+        };
+
+        // Odd lengths put the extra cell in the upper half
+        let chunk = Chunk::new(v(0, 0, 0), v(3, 2, 1));
+        assert_eq!(
+            vec![
+                (v(0, 0, 0), v(1, 1, 1)),
+                (v(1, 0, 0), v(2, 1, 1)),
+                (v(0, 1, 0), v(1, 1, 1)),
+                (v(1, 1, 0), v(2, 1, 1))
+            ],
+            bounds(chunk.octants())
+        );
+
+        let cell = Chunk::new(v(4, 5, 6), v(1, 1, 1));
+        assert_eq!(vec![(v(4, 5, 6), v(1, 1, 1))], bounds(cell.octants()));
+
+        let cube = Chunk::new(v(-2, -2, -2), v(4, 4, 4));
+        let mut cells: Vec<Vector3i> = cube
+            .octants()
+            .iter()
+            .flat_map(|c| c._get_all_cells())
+            .collect();
+        cells.sort();
+        let mut expected = cube._get_all_cells();
+        expected.sort();
+        assert_eq!(expected, cells);
+    }
+
+    #[test]
+    fn test_grown_and_clipped() {
+        let v = |x, y, z| Vector3i { x, y, z };
+        let map = Chunk::new(v(0, 0, 0), v(10, 10, 10));
+
+        let grown = Chunk::new(v(0, 4, 8), v(2, 2, 2)).grown(v(2, 1, 0));
+        assert_eq!((v(-2, 3, 8), v(6, 4, 2)), (grown.position(), grown.size()));
+
+        let clipped = grown.clipped_to(&map);
+        assert_eq!(
+            (v(0, 3, 8), v(4, 4, 2)),
+            (clipped.position(), clipped.size())
+        );
+
+        let outside = Chunk::new(v(20, 0, 0), v(2, 2, 2)).clipped_to(&map);
+        assert!(outside.size().x <= 0);
+    }
 }
@@ -1,19 +1,88 @@
-use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, SyncSender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use godot::prelude::*;
 
 use crate::models::{
-    collapser_action::{CollapserAction, CollapserActionType},
+    chunk_order::ChunkOrder,
+    collapser_action::{CollapserAction, CollapserCommand},
     collapser_state::CollapserState,
-    driver_update::DriverUpdate,
+    driver_update::{DriverUpdate, MapReset},
+    map_config::MapConfig,
+    map_snapshot::MapSnapshot,
+    prototype::Prototype,
+    prototype_selector::PrototypeSelector,
+    retry_policy::RetryPolicy,
+    weight_field::WeightField,
 };
 
-use super::map::Map;
+use super::{map::Map, outbox::Outbox};
+
+// How often a worker with a backlog retries sending it while otherwise idle
+const FLUSH_INTERVAL: Duration = Duration::from_millis(10);
+
+// The latest focus from the driver, if it's changed since the worker last looked. The camera
+//  moves every frame, so this is shared directly rather than queued as commands.
+pub type FocusSlot = Arc<Mutex<Option<Vector3i>>>;
+
+// Entry point for the worker thread. An invalid initial configuration, or a panic anywhere in the
+//  collapser, is reported to the driver as an error update rather than silently ending the thread.
+pub fn run_worker(
+    sender: SyncSender<DriverUpdate>,
+    receiver: Receiver<CollapserAction>,
+    focus: FocusSlot,
+    config: MapConfig,
+    prototypes: Vec<Prototype>,
+) {
+    let error_sender = sender.clone();
+    let result = panic::catch_unwind(AssertUnwindSafe(move || {
+        let mut collapser = LWFCCollapser::new(sender, receiver, focus, config, prototypes)?;
+        collapser.run();
+        Ok(())
+    }));
+
+    let message = match result {
+        Ok(Ok(())) => return,
+        Ok(Err(message)) => message,
+        Err(payload) => panic_message(payload),
+    };
+    // Blocks until there's room; the driver keeps draining while it waits for us to exit.
+    // If this fails too, the driver is already gone and there's nobody left to tell
+    let _ = error_sender.send(DriverUpdate::new_error(message));
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        return message.to_string();
+    }
+    if let Some(message) = payload.downcast_ref::<String>() {
+        return message.clone();
+    }
+    "Worker thread panicked".into()
+}
 
 pub struct LWFCCollapser {
     state: CollapserState,
+    // Request id and remaining iterations of an in-progress Step command
+    pending_step: Option<(u64, u32)>,
+    collapses_per_second: f64,
+    next_collapse_at: Option<Instant>,
+    // Kept here as well as on the map so they carry over when the map is replaced
+    chunk_order: ChunkOrder,
+    focus: Vector3i,
+    focus_slot: FocusSlot,
+    retry_policy: RetryPolicy,
+    weight_field: WeightField,
+    // Every SetWeights and SetEnabled that was applied, in order, to apply again to a new map
+    weights: Vec<(PrototypeSelector, f32)>,
+    enabled: Vec<(Vec<PrototypeSelector>, bool)>,
+    // Every new map starts from these, rather than from the old map's possibly reweighted copy
+    prototypes: Vec<Prototype>,
 
-    sender: Sender<DriverUpdate>,
+    outbox: Outbox,
     receiver: Receiver<CollapserAction>,
 
     map: Map,
@@ -21,34 +90,49 @@ pub struct LWFCCollapser {
 
 impl LWFCCollapser {
     pub fn new(
-        sender: Sender<DriverUpdate>,
+        sender: SyncSender<DriverUpdate>,
         receiver: Receiver<CollapserAction>,
-        map_size: Vector3i,
-        chunk_size: Vector3i,
-        chunk_overlap: i32,
-    ) -> Self {
+        focus_slot: FocusSlot,
+        config: MapConfig,
+        prototypes: Vec<Prototype>,
+    ) -> Result<Self, String> {
         let state = CollapserState::IDLE;
-        let map = Map::new(map_size, chunk_size, chunk_overlap);
-        Self {
+        let map = Map::new(config, prototypes.clone(), rand::random())?;
+        Ok(Self {
             state,
-            sender,
+            pending_step: None,
+            collapses_per_second: 0.0,
+            next_collapse_at: None,
+            chunk_order: ChunkOrder::Linear,
+            focus: Vector3i::ZERO,
+            focus_slot,
+            retry_policy: RetryPolicy::default(),
+            weight_field: WeightField::default(),
+            weights: vec![],
+            enabled: vec![],
+            prototypes,
+            outbox: Outbox::new(sender),
             receiver,
             map,
-        }
+        })
     }
 
     pub fn run(&mut self) {
         godot_print!("Starting run in thread.");
-        if let Some(update) = self.map.initialize() {
-            self.post_changes(update);
-        }
+        let update = self.map.initialize();
+        self.post_changes(update);
 
         loop {
+            self.take_focus();
+            if !self.outbox.flush() {
+                self.state = CollapserState::STOPPED;
+            }
+
             if self.state == CollapserState::STOPPED {
                 break;
             }
 
-            if self.state == CollapserState::IDLE {
+            if self.state == CollapserState::IDLE || self.state == CollapserState::COMPLETED {
                 self.wait_for_message();
             }
 
@@ -58,7 +142,32 @@ impl LWFCCollapser {
         }
     }
 
+    fn take_focus(&mut self) {
+        let focus = match self.focus_slot.lock() {
+            Ok(mut slot) => slot.take(),
+            // The driver panicked while holding it, so it isn't going to send any more
+            Err(_) => None,
+        };
+        if let Some(focus) = focus {
+            self.focus = focus;
+            self.map.set_focus(focus);
+        }
+    }
+
     fn wait_for_message(&mut self) {
+        // Don't block indefinitely while there are still updates waiting to go out
+        if !self.outbox.is_empty() {
+            match self.receiver.recv_timeout(FLUSH_INTERVAL) {
+                Ok(action) => self.on_message_received(action),
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => {
+                    godot_error!("Disconnected in thread (IDLE). Exiting.");
+                    self.stop();
+                }
+            }
+            return;
+        }
+
         godot_print!("Waiting for message in thread");
         match self.receiver.recv() {
             Ok(action) => self.on_message_received(action),
@@ -70,6 +179,19 @@ impl LWFCCollapser {
     }
 
     fn check_for_message(&mut self) {
+        // When rate limited, wait out the remaining time while still listening for commands
+        if let Some(delay) = self.time_until_next_collapse() {
+            match self.receiver.recv_timeout(delay) {
+                Ok(action) => self.on_message_received(action),
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => {
+                    godot_error!("Disconnected in thread (PROCESSING). Exiting.");
+                    self.stop()
+                }
+            }
+            return;
+        }
+
         match self.receiver.try_recv() {
             Ok(action) => self.on_message_received(action),
             Err(e) => match e {
@@ -84,14 +206,23 @@ impl LWFCCollapser {
         }
     }
 
+    fn time_until_next_collapse(&self) -> Option<Duration> {
+        let next_collapse_at = self.next_collapse_at?;
+        next_collapse_at
+            .checked_duration_since(Instant::now())
+            .filter(|delay| !delay.is_zero())
+    }
+
     fn collapse_next(&mut self) -> Option<()> {
+        if self.collapses_per_second > 0.0 {
+            let interval = Duration::from_secs_f64(1.0 / self.collapses_per_second);
+            self.next_collapse_at = Some(Instant::now() + interval);
+        }
+
         let update = self.map.collapse_next()?;
         if let Some(state) = update.new_state {
-            // TODO - apply the state update here if we have one.
-            // the collapser should have responsibility to stop itself
-            // plus the driver doesnt currently do anything with status updates.
-            if state == CollapserState::STOPPED {
-                self.stop();
+            if state == CollapserState::COMPLETED {
+                self.complete();
                 return None;
             }
         }
@@ -100,17 +231,110 @@ impl LWFCCollapser {
         // Sometimes you'll post one change per tick and the main thread never catches up.
         // Sometimes you'll post one large collapse and it will try to process the entire thing in one tick
         self.post_changes(update);
+        self.count_step();
         None
     }
 
     fn on_message_received(&mut self, action: CollapserAction) {
-        godot_print!("Message received in thread: {:?}", action);
-        match action.action_type {
-            CollapserActionType::NOOP => godot_print!("noop!"),
-            CollapserActionType::START => self.start(),
-            CollapserActionType::PAUSE => self.idle(),
-            CollapserActionType::STOP => self.stop(),
-        }
+        godot_print!(
+            "Message received in thread: {} (request {})",
+            action.command.name(),
+            action.request_id
+        );
+        let request_id = action.request_id;
+        let result = match action.command {
+            CollapserCommand::Start => self.start(),
+            CollapserCommand::Pause => {
+                self.interrupt_step();
+                self.idle();
+                Ok(())
+            }
+            CollapserCommand::Stop => {
+                self.interrupt_step();
+                self.stop();
+                Ok(())
+            }
+            CollapserCommand::Step { count } => return self.step(request_id, count),
+            CollapserCommand::SetSpeed {
+                collapses_per_second,
+            } => self.set_speed(collapses_per_second),
+            CollapserCommand::Reset { seed } => {
+                Map::new(self.map.config.clone(), self.prototypes.clone(), seed).map(|map| {
+                    self.replace_map(request_id, map);
+                    let update = self.map.initialize();
+                    self.post_changes(update);
+                    self.resume_if_completed();
+                })
+            }
+            CollapserCommand::Resize { config } => self.resize(request_id, config),
+            CollapserCommand::PinCells { cells } => {
+                self.map.pin_cells(cells).map(|u| self.post_changes(u))
+            }
+            CollapserCommand::RegenerateRegion { position, size } => self
+                .map
+                .regenerate_region(position, size)
+                .map(|u| self.post_changes(u))
+                .map(|_| self.resume_if_completed()),
+            CollapserCommand::SetWeights { weights } => self
+                .map
+                .set_weights(weights.clone())
+                .map(|_| self.weights.extend(weights)),
+            CollapserCommand::SetEnabled { selectors, enabled } => {
+                self.map.set_enabled(selectors.clone(), enabled).map(|u| {
+                    self.post_changes(u);
+                    self.enabled.push((selectors, enabled));
+                })
+            }
+            CollapserCommand::AddTagConstraint { constraint } => self
+                .map
+                .add_tag_constraint(constraint)
+                .map(|u| self.post_changes(u)),
+            CollapserCommand::ClearTagConstraints => self
+                .map
+                .clear_tag_constraints()
+                .map(|u| self.post_changes(u)),
+            CollapserCommand::AddWeightLayer { layer } => {
+                let mut field = self.weight_field.clone();
+                field.layers.push(layer);
+                self.map
+                    .set_weight_field(field.clone())
+                    .map(|_| self.weight_field = field)
+            }
+            CollapserCommand::ClearWeightField => {
+                self.weight_field = WeightField::default();
+                self.map.set_weight_field(WeightField::default())
+            }
+            CollapserCommand::SetChunkOrder { order } => {
+                self.chunk_order = order.clone();
+                self.map.set_chunk_order(order);
+                Ok(())
+            }
+            CollapserCommand::SetRetryPolicy { policy } => {
+                self.retry_policy = policy;
+                self.map.set_retry_policy(policy);
+                Ok(())
+            }
+            CollapserCommand::SetPrototypes { prototypes } => {
+                self.set_prototypes(request_id, prototypes)
+            }
+            CollapserCommand::Save { path } => self.map.snapshot().save(&path),
+            CollapserCommand::Load { path, as_fill } => MapSnapshot::load(&path)
+                .and_then(|snapshot| {
+                    let prototypes = self.prototypes.clone();
+                    if as_fill {
+                        Map::from_fill(snapshot, prototypes, rand::random())
+                    } else {
+                        Map::from_snapshot(snapshot, prototypes, rand::random())
+                    }
+                })
+                .map(|map| {
+                    self.replace_map(request_id, map);
+                    self.post_changes(self.map.full_update());
+                    self.resume_if_completed();
+                }),
+        };
+
+        self.acknowledge(request_id, result);
     }
 
     fn idle(&mut self) {
@@ -118,9 +342,15 @@ impl LWFCCollapser {
         self.post_changes(DriverUpdate::new_state(self.state));
     }
 
-    fn start(&mut self) {
+    fn start(&mut self) -> Result<(), String> {
+        self.interrupt_step();
+        if self.state == CollapserState::COMPLETED {
+            return Err("Map is already completed".into());
+        }
+
         self.state = CollapserState::PROCESSING;
         self.post_changes(DriverUpdate::new_state(self.state));
+        Ok(())
     }
 
     fn stop(&mut self) {
@@ -128,7 +358,140 @@ impl LWFCCollapser {
         self.post_changes(DriverUpdate::new_state(self.state));
     }
 
+    fn complete(&mut self) {
+        self.state = CollapserState::COMPLETED;
+        self.post_changes(DriverUpdate::new_state(self.state));
+        if let Some((request_id, _)) = self.pending_step.take() {
+            self.acknowledge(request_id, Ok(()));
+        }
+    }
+
+    // Steps are acknowledged once they've run, not when they're received
+    fn step(&mut self, request_id: u64, count: u32) {
+        if let Err(e) = self.start() {
+            self.acknowledge(request_id, Err(e));
+            return;
+        }
+
+        if count == 0 {
+            self.idle();
+            self.acknowledge(request_id, Ok(()));
+            return;
+        }
+
+        self.pending_step = Some((request_id, count));
+    }
+
+    fn count_step(&mut self) {
+        match self.pending_step {
+            Some((request_id, remaining)) if remaining <= 1 => {
+                self.pending_step = None;
+                self.idle();
+                self.acknowledge(request_id, Ok(()));
+            }
+            Some((request_id, remaining)) => self.pending_step = Some((request_id, remaining - 1)),
+            None => (),
+        }
+    }
+
+    fn interrupt_step(&mut self) {
+        if let Some((request_id, _)) = self.pending_step.take() {
+            self.acknowledge(request_id, Err("Interrupted by another command".into()));
+        }
+    }
+
+    fn set_speed(&mut self, collapses_per_second: f64) -> Result<(), String> {
+        if !collapses_per_second.is_finite() || collapses_per_second < 0.0 {
+            return Err(format!(
+                "Collapses per second must be zero or positive, got {}",
+                collapses_per_second
+            ));
+        }
+
+        self.collapses_per_second = collapses_per_second;
+        self.next_collapse_at = None;
+        Ok(())
+    }
+
+    fn resize(&mut self, request_id: u64, config: MapConfig) -> Result<(), String> {
+        let map = Map::new(config, self.prototypes.clone(), rand::random())?;
+        self.replace_map(request_id, map);
+        let update = self.map.initialize();
+        self.post_changes(update);
+        self.resume_if_completed();
+        Ok(())
+    }
+
+    // The old prototypes are kept if the new ones can't build a map
+    fn set_prototypes(
+        &mut self,
+        request_id: u64,
+        prototypes: Vec<Prototype>,
+    ) -> Result<(), String> {
+        let map = Map::new(self.map.config.clone(), prototypes.clone(), rand::random())?;
+        self.prototypes = prototypes;
+        self.replace_map(request_id, map);
+        let update = self.map.initialize();
+        self.post_changes(update);
+        self.resume_if_completed();
+        Ok(())
+    }
+
+    // Swap in a new map and tell the driver to throw away everything it has for the old one.
+    // Any in-progress step belonged to the old map, so it's dropped too.
+    fn replace_map(&mut self, request_id: u64, map: Map) {
+        self.interrupt_step();
+        self.map = map;
+        self.map.set_chunk_order(self.chunk_order.clone());
+        self.map.set_focus(self.focus);
+        self.map.set_retry_policy(self.retry_policy);
+        // A field whose selectors don't match the new prototypes can't be kept
+        if let Err(e) = self.map.set_weight_field(self.weight_field.clone()) {
+            godot_error!("Dropping the weight field: {}", e);
+            self.weight_field = WeightField::default();
+        }
+        self.post_changes(DriverUpdate::new_reset(MapReset {
+            request_id,
+            config: self.map.config.clone(),
+        }));
+
+        // Like the weight field, overrides that don't match the new prototypes are dropped
+        for (selector, weight) in std::mem::take(&mut self.weights) {
+            match self.map.set_weights(vec![(selector.clone(), weight)]) {
+                Ok(()) => self.weights.push((selector, weight)),
+                Err(e) => godot_error!("Dropping a weight override: {}", e),
+            }
+        }
+        for (selectors, enabled) in std::mem::take(&mut self.enabled) {
+            match self.map.set_enabled(selectors.clone(), enabled) {
+                Ok(update) => {
+                    self.post_changes(update);
+                    self.enabled.push((selectors, enabled));
+                }
+                Err(e) => godot_error!("Dropping an enabled override: {}", e),
+            }
+        }
+    }
+
+    // A completed map that gets new work should pick it up without another START
+    fn resume_if_completed(&mut self) {
+        if self.state == CollapserState::COMPLETED && !self.map.is_completed() {
+            self.state = CollapserState::PROCESSING;
+            self.post_changes(DriverUpdate::new_state(self.state));
+        }
+    }
+
+    fn acknowledge(&mut self, request_id: u64, result: Result<(), String>) {
+        if let Err(e) = &result {
+            godot_error!("Command {} failed: {}", request_id, e);
+        }
+        self.post_changes(DriverUpdate::new_command_result(request_id, result));
+    }
+
+    // A failed send means the driver has been freed, so there's no reason to keep running
     fn post_changes(&mut self, update: DriverUpdate) {
-        self.sender.send(update).unwrap();
+        if !self.outbox.post(update) {
+            self.state = CollapserState::STOPPED;
+        }
     }
 }
@@ -0,0 +1,160 @@
+use std::collections::{BTreeMap, HashMap};
+
+use godot::prelude::*;
+use rand::Rng;
+
+// Cells of the current chunk that still need collapsing, bucketed by entropy.
+// Ties are broken by a cell's rank in the order indices_in walks the chunk, y then x then z.
+//  Picking the n-th ranked cell of the lowest bucket therefore makes exactly the same choice, with
+//  the same rng draw, as scanning the chunk for its lowest-entropy cells would.
+// Each bucket counts its cells by rank in a Fenwick tree, so both updates and picks are O(log n)
+//  however large the bucket.
+#[derive(Default)]
+pub struct EntropyQueue {
+    origin: Vector3i,
+    size: Vector3i,
+    buckets: BTreeMap<usize, Bucket>,
+    // Entropy and rank of every queued cell, by storage index
+    queued: HashMap<usize, (usize, usize)>,
+    // Storage index of every queued cell, by rank
+    ranked: HashMap<usize, usize>,
+}
+
+impl EntropyQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Empties the queue, ready for the cells of the box at `origin` with `size`
+    pub fn reset(&mut self, origin: Vector3i, size: Vector3i) {
+        self.clear();
+        self.origin = origin;
+        self.size = size;
+    }
+
+    pub fn clear(&mut self) {
+        self.buckets.clear();
+        self.queued.clear();
+        self.ranked.clear();
+    }
+
+    // Track a cell's new entropy. Cells with one or no possibilities left are dropped from the
+    //  queue, as are cells outside the box.
+    pub fn update(&mut self, index: usize, position: Vector3i, entropy: usize) {
+        if let Some((old_entropy, rank)) = self.queued.get(&index).copied() {
+            if old_entropy == entropy {
+                return;
+            }
+            self.remove_from_bucket(old_entropy, rank);
+            self.queued.remove(&index);
+            self.ranked.remove(&rank);
+        }
+
+        let Some(rank) = self.rank_of(position) else {
+            return;
+        };
+        if entropy <= 1 {
+            return;
+        }
+
+        let volume = self.volume();
+        self.buckets
+            .entry(entropy)
+            .or_insert_with(|| Bucket::new(volume))
+            .add(rank, 1);
+        self.queued.insert(index, (entropy, rank));
+        self.ranked.insert(rank, index);
+    }
+
+    // A random cell out of those with the lowest entropy
+    pub fn pick<R: Rng>(&self, rng: &mut R) -> Option<usize> {
+        let (_, bucket) = self.buckets.iter().next()?;
+        let selected = rng.gen_range(0..bucket.len);
+        self.ranked.get(&bucket.select(selected)).copied()
+    }
+
+    fn remove_from_bucket(&mut self, entropy: usize, rank: usize) {
+        let Some(bucket) = self.buckets.get_mut(&entropy) else {
+            return;
+        };
+        bucket.add(rank, -1);
+        if bucket.len == 0 {
+            self.buckets.remove(&entropy);
+        }
+    }
+
+    fn volume(&self) -> usize {
+        (self.size.x.max(0) as usize)
+            * (self.size.y.max(0) as usize)
+            * (self.size.z.max(0) as usize)
+    }
+
+    fn rank_of(&self, position: Vector3i) -> Option<usize> {
+        let local = position - self.origin;
+        if local.x < 0
+            || local.x >= self.size.x
+            || local.y < 0
+            || local.y >= self.size.y
+            || local.z < 0
+            || local.z >= self.size.z
+        {
+            return None;
+        }
+        Some(((local.y * self.size.x + local.x) * self.size.z + local.z) as usize)
+    }
+}
+
+// The cells of one entropy, as a Fenwick tree over ranks. Only nodes with cells under them are
+//  stored, so a bucket takes space for its own cells rather than for the whole box.
+struct Bucket {
+    len: usize,
+    volume: usize,
+    nodes: HashMap<usize, usize>,
+}
+
+impl Bucket {
+    fn new(volume: usize) -> Self {
+        Self {
+            len: 0,
+            volume,
+            nodes: HashMap::new(),
+        }
+    }
+
+    fn add(&mut self, rank: usize, delta: isize) {
+        self.len = self.len.wrapping_add_signed(delta);
+        // Fenwick trees count from 1
+        let mut node = rank + 1;
+        while node <= self.volume {
+            let count = self.nodes.entry(node).or_default();
+            *count = count.wrapping_add_signed(delta);
+            if *count == 0 {
+                self.nodes.remove(&node);
+            }
+            node += node & node.wrapping_neg();
+        }
+    }
+
+    // The rank of the n-th cell in the bucket, counting from 0
+    fn select(&self, n: usize) -> usize {
+        let mut node = 0;
+        let mut remaining = n + 1;
+        let mut step = if self.volume == 0 {
+            0
+        } else {
+            1 << self.volume.ilog2()
+        };
+        while step > 0 {
+            let next = node + step;
+            if next <= self.volume {
+                let count = self.nodes.get(&next).copied().unwrap_or(0);
+                if count < remaining {
+                    node = next;
+                    remaining -= count;
+                }
+            }
+            step >>= 1;
+        }
+        node
+    }
+}
@@ -0,0 +1,81 @@
+#[cfg(test)]
+mod tests {
+    use godot::builtin::Vector3i;
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+    use crate::worker::{entropy_queue::EntropyQueue, grid::Grid, storage::CellStorage};
+
+    // What select_lowest_entropy used to do: scan the whole box and pick a random lowest cell
+    fn scan<R: Rng>(entropies: &Grid<usize>, rng: &mut R) -> Option<usize> {
+        let mut lowest_entropy = usize::MAX;
+        let mut lowest_entropy_cells = vec![];
+        for index in entropies.indices_in(Vector3i::ZERO, Vector3i { x: 5, y: 2, z: 5 }) {
+            let entropy = *entropies.at(index);
+            if entropy <= 1 || entropy > lowest_entropy {
+                continue;
+            }
+            if entropy < lowest_entropy {
+                lowest_entropy = entropy;
+                lowest_entropy_cells = vec![index];
+            } else {
+                lowest_entropy_cells.push(index);
+            }
+        }
+
+        if lowest_entropy_cells.is_empty() {
+            return None;
+        }
+        Some(lowest_entropy_cells[rng.gen_range(0..lowest_entropy_cells.len())])
+    }
+
+    #[test]
+    fn test_matches_full_scan() {
+        let size = Vector3i { x: 5, y: 2, z: 5 };
+        let mut setup_rng = SmallRng::seed_from_u64(7);
+        let mut entropies = Grid::new(Vector3i::ZERO, size, |_| setup_rng.gen_range(0..6usize));
+
+        let mut queue = EntropyQueue::new();
+        queue.reset(Vector3i::ZERO, size);
+        for index in 0..entropies.len() {
+            queue.update(index, entropies.position_of(index), *entropies.at(index));
+        }
+
+        let mut scan_rng = SmallRng::seed_from_u64(42);
+        let mut queue_rng = SmallRng::seed_from_u64(42);
+        loop {
+            let expected = scan(&entropies, &mut scan_rng);
+            assert_eq!(expected, queue.pick(&mut queue_rng));
+            let Some(picked) = expected else {
+                break;
+            };
+
+            // Collapse the picked cell and shrink a couple of others, like propagation would
+            *entropies.at_mut(picked) = 1;
+            queue.update(picked, entropies.position_of(picked), 1);
+            for _ in 0..2 {
+                let other = setup_rng.gen_range(0..entropies.len());
+                let entropy = entropies.at(other).saturating_sub(1);
+                *entropies.at_mut(other) = entropy;
+                queue.update(other, entropies.position_of(other), entropy);
+            }
+        }
+    }
+
+    #[test]
+    fn test_reinserts_cells_that_regain_entropy() {
+        let mut queue = EntropyQueue::new();
+        queue.reset(Vector3i::ZERO, Vector3i { x: 2, y: 1, z: 1 });
+        let position = Vector3i { x: 1, y: 0, z: 0 };
+        let mut rng = SmallRng::seed_from_u64(0);
+
+        queue.update(3, position, 4);
+        queue.update(3, position, 1);
+        assert_eq!(None, queue.pick(&mut rng));
+
+        queue.update(3, position, 5);
+        assert_eq!(Some(3), queue.pick(&mut rng));
+
+        queue.clear();
+        assert_eq!(None, queue.pick(&mut rng));
+    }
+}
@@ -0,0 +1,137 @@
+use std::ops::{Index, IndexMut};
+
+use godot::prelude::*;
+
+use super::storage::CellStorage;
+
+// The six cardinal directions, in the order used by the neighbour table
+pub const DIRECTIONS: [Vector3i; 6] = [
+    Vector3i::UP,
+    Vector3i::DOWN,
+    Vector3i::RIGHT,
+    Vector3i::LEFT,
+    Vector3i::FORWARD,
+    Vector3i::BACK,
+];
+
+// Flat, contiguous storage for one value per cell of a box-shaped map. Cells are laid out in
+//  [y][x][z] order, so z is the fastest-moving axis and a row of cells along z is contiguous in
+//  memory.
+pub struct Grid<T> {
+    origin: Vector3i,
+    size: Vector3i,
+    cells: Vec<T>,
+    // For each cell, the index of its neighbour in each of DIRECTIONS, if that neighbour is in
+    //  bounds
+    neighbors: Vec<[Option<usize>; 6]>,
+}
+
+impl<T> Grid<T> {
+    pub fn new<F: FnMut(Vector3i) -> T>(origin: Vector3i, size: Vector3i, mut f: F) -> Self {
+        let size = Vector3i {
+            x: size.x.max(0),
+            y: size.y.max(0),
+            z: size.z.max(0),
+        };
+        let volume = (size.x * size.y * size.z) as usize;
+
+        let mut grid = Self {
+            origin,
+            size,
+            cells: Vec::with_capacity(volume),
+            neighbors: Vec::with_capacity(volume),
+        };
+        for index in 0..volume {
+            let position = grid.position_of(index);
+            grid.cells.push(f(position));
+            grid.neighbors
+                .push(DIRECTIONS.map(|direction| grid.index_of(position + direction)));
+        }
+
+        grid
+    }
+}
+
+impl<T> CellStorage<T> for Grid<T> {
+    fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    fn index_of(&self, position: Vector3i) -> Option<usize> {
+        let local = position - self.origin;
+        if local.x < 0
+            || local.x >= self.size.x
+            || local.y < 0
+            || local.y >= self.size.y
+            || local.z < 0
+            || local.z >= self.size.z
+        {
+            return None;
+        }
+        Some(((local.y * self.size.x + local.x) * self.size.z + local.z) as usize)
+    }
+
+    fn position_of(&self, index: usize) -> Vector3i {
+        let index = index as i32;
+        let z = index % self.size.z;
+        let x = (index / self.size.z) % self.size.x;
+        let y = index / (self.size.z * self.size.x);
+        self.origin + Vector3i { x, y, z }
+    }
+
+    fn at(&self, index: usize) -> &T {
+        &self.cells[index]
+    }
+
+    fn at_mut(&mut self, index: usize) -> &mut T {
+        &mut self.cells[index]
+    }
+
+    fn neighbors(&self, index: usize) -> [Option<usize>; 6] {
+        self.neighbors[index]
+    }
+
+    fn indices_in(
+        &self,
+        position: Vector3i,
+        size: Vector3i,
+    ) -> Box<dyn Iterator<Item = usize> + '_> {
+        let local = position - self.origin;
+        let start = Vector3i {
+            x: local.x.max(0),
+            y: local.y.max(0),
+            z: local.z.max(0),
+        };
+        let end = Vector3i {
+            x: (local.x + size.x).min(self.size.x),
+            y: (local.y + size.y).min(self.size.y),
+            z: (local.z + size.z).min(self.size.z),
+        };
+        let grid_size = self.size;
+
+        Box::new((start.y..end.y).flat_map(move |y| {
+            (start.x..end.x).flat_map(move |x| {
+                let row = (y * grid_size.x + x) * grid_size.z;
+                (start.z..end.z).map(move |z| (row + z) as usize)
+            })
+        }))
+    }
+
+    fn indices(&self) -> Box<dyn Iterator<Item = usize> + '_> {
+        Box::new(0..self.cells.len())
+    }
+}
+
+impl<T> Index<usize> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        &self.cells[index]
+    }
+}
+
+impl<T> IndexMut<usize> for Grid<T> {
+    fn index_mut(&mut self, index: usize) -> &mut T {
+        &mut self.cells[index]
+    }
+}
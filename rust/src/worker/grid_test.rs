@@ -0,0 +1,89 @@
+#[cfg(test)]
+mod tests {
+    use godot::builtin::Vector3i;
+
+    use crate::worker::{
+        grid::{Grid, DIRECTIONS},
+        storage::CellStorage,
+    };
+
+    fn v(x: i32, y: i32, z: i32) -> Vector3i {
+        Vector3i { x, y, z }
+    }
+
+    #[test]
+    fn test_index_round_trip() {
+        let grid = Grid::new(Vector3i::ZERO, v(4, 3, 5), |position| position);
+        assert_eq!(60, grid.len());
+
+        for index in 0..grid.len() {
+            let position = grid.position_of(index);
+            assert_eq!(position, grid[index]);
+            assert_eq!(Some(index), grid.index_of(position));
+        }
+
+        assert_eq!(None, grid.index_of(v(4, 0, 0)));
+        assert_eq!(None, grid.index_of(v(0, -1, 0)));
+        assert_eq!(None, grid.get(v(0, 0, 5)));
+    }
+
+    #[test]
+    fn test_z_is_contiguous() {
+        let grid = Grid::new(Vector3i::ZERO, v(4, 3, 5), |_| ());
+        assert_eq!(Some(0), grid.index_of(v(0, 0, 0)));
+        assert_eq!(Some(1), grid.index_of(v(0, 0, 1)));
+        assert_eq!(Some(5), grid.index_of(v(1, 0, 0)));
+        assert_eq!(Some(20), grid.index_of(v(0, 1, 0)));
+    }
+
+    #[test]
+    fn test_neighbors() {
+        let grid = Grid::new(Vector3i::ZERO, v(3, 3, 3), |position| position);
+
+        let center = grid.index_of(v(1, 1, 1)).unwrap();
+        let neighbors = grid.neighbors(center);
+        for (direction, neighbor) in DIRECTIONS.iter().zip(neighbors.iter()) {
+            assert_eq!(Some(v(1, 1, 1) + *direction), neighbor.map(|i| grid[i]));
+        }
+
+        let corner = grid.index_of(v(0, 0, 0)).unwrap();
+        assert_eq!(3, grid.neighbors(corner).iter().flatten().count());
+    }
+
+    #[test]
+    fn test_indices_in_is_clipped() {
+        let grid = Grid::new(Vector3i::ZERO, v(4, 1, 4), |position| position);
+
+        let inside: Vec<Vector3i> = grid
+            .indices_in(v(1, 0, 2), v(2, 1, 2))
+            .map(|i| grid[i])
+            .collect();
+        assert_eq!(vec![v(1, 0, 2), v(1, 0, 3), v(2, 0, 2), v(2, 0, 3)], inside);
+
+        let clipped: Vec<Vector3i> = grid
+            .indices_in(v(-1, -1, 3), v(2, 5, 5))
+            .map(|i| grid[i])
+            .collect();
+        assert_eq!(vec![v(0, 0, 3)], clipped);
+
+        assert_eq!(0, grid.indices_in(v(5, 0, 0), v(2, 1, 2)).count());
+    }
+
+    #[test]
+    fn test_negative_origin() {
+        let grid = Grid::new(v(-2, 0, -3), v(4, 1, 4), |position| position);
+
+        assert_eq!(Some(0), grid.index_of(v(-2, 0, -3)));
+        assert_eq!(None, grid.index_of(v(2, 0, 0)));
+        assert_eq!(None, grid.index_of(v(-3, 0, 0)));
+        for index in 0..grid.len() {
+            assert_eq!(grid[index], grid.position_of(index));
+        }
+
+        let clipped: Vec<Vector3i> = grid
+            .indices_in(v(-5, 0, -5), v(4, 1, 3))
+            .map(|i| grid[i])
+            .collect();
+        assert_eq!(vec![v(-2, 0, -3)], clipped);
+    }
+}
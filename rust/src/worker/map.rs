@@ -1,132 +1,1204 @@
-use godot::{builtin::Vector3i, engine::utilities::ceili, log::godot_print};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::time::Instant;
+
+use godot::builtin::Vector3i;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
 
 use crate::models::{
+    chunk_event::{ChunkEvent, ChunkEventKind, ChunkStats, SeamViolation},
+    chunk_order::ChunkOrder,
     collapser_state::CollapserState,
     driver_update::{CellChange, DriverUpdate},
+    map_config::{MapConfig, SolverMode},
+    map_snapshot::MapSnapshot,
     prototype::Prototype,
+    prototype_selector::PrototypeSelector,
+    retry_policy::{Escalation, RetryPolicy},
+    tag_constraint::TagConstraint,
+    weight_field::WeightField,
+};
+
+use super::{
+    cell::Cell,
+    chunk::Chunk,
+    chunk_layout::ChunkLayout,
+    entropy_queue::EntropyQueue,
+    scheduler, seams,
+    storage::{new_storage, CellStorage},
 };
 
-use super::{cell::Cell, chunk::Chunk};
+// How many cells around a seam violation get re-solved to repair it
+const SEAM_REPAIR_RADIUS: i32 = 1;
+// A cell that's still mismatched after this many repairs is left alone, so a seam that can't be
+//  fixed doesn't keep the worker busy forever
+const MAX_SEAM_REPAIRS: u32 = 3;
+// Weighted entropies this close together share a place in the entropy queue
+const ENTROPY_RESOLUTION: f32 = 1000.0;
 
 pub struct Map {
-    pub size: Vector3i,
-    cells: Vec<Vec<Vec<Cell>>>,
+    pub config: MapConfig,
+    cells: Box<dyn CellStorage<Cell>>,
     chunks: Vec<Chunk>,
-    chunk_overlap: i32,
-    current_chunk: usize,
+    // Chunk bookkeeping, as indices into `chunks`. Every chunk is in exactly one of these.
+    processed_chunks: Vec<usize>,
+    current_chunk: Option<usize>,
+    pending_chunks: Vec<usize>,
+    // Regions asked for explicitly are collapsed before anything the scheduler would pick
+    requested_chunks: VecDeque<usize>,
+    chunk_order: ChunkOrder,
+    focus: Vector3i,
+    // Stats for the current chunk
+    chunk_stats: ChunkStats,
+    chunk_started_at: Instant,
+    // Cells of the current chunk that are left to collapse
+    entropy_queue: EntropyQueue,
+    // Cells whose possibilities haven't been propagated to their neighbours yet
+    dirty: BTreeSet<usize>,
+    // How many times a repair has been queued for each mismatched seam cell
+    seam_repairs: HashMap<Vector3i, u32>,
+    // The current chunk's cells as they were before it was reset, to put back if it fails
+    chunk_backup: Vec<(Vector3i, Vec<Prototype>)>,
+    // Failed attempts so far, for chunks that have failed at least once
+    chunk_attempts: HashMap<usize, u32>,
+    retry_policy: RetryPolicy,
+    // Chunks that replaced a chunk which ran out of retries. They aren't escalated again.
+    escalated_chunks: HashSet<usize>,
     proto_data: Vec<Prototype>,
+    weight_field: WeightField,
+    // Ids of prototypes that cells reset from now on won't be offered
+    disabled: HashSet<String>,
+    tag_constraints: Vec<TagConstraint>,
+    pinned: HashMap<Vector3i, Prototype>,
+    // Set while an edit propagates across the whole map, so that its contradictions aren't charged
+    //  to the current chunk
+    edit: Option<EditJournal>,
+    rng: SmallRng,
+}
+
+// The cells an edit has changed, as they were before it, and the cells it would have left with no
+//  possibilities
+#[derive(Default)]
+struct EditJournal {
+    before: HashMap<usize, Vec<Prototype>>,
+    contradictions: Vec<Vector3i>,
 }
 
 impl Map {
-    pub fn new(size: Vector3i, chunk_size: Vector3i, chunk_overlap: i32) -> Self {
-        let proto_data = Prototype::load();
-        godot_print!("Loaded {} prototypes", proto_data.len());
-        let cells = generate_cells(size, &proto_data);
-        let chunks = generate_chunks(size, chunk_size, chunk_overlap);
-        Self {
-            size,
+    pub fn new(config: MapConfig, proto_data: Vec<Prototype>, seed: u64) -> Result<Self, String> {
+        let cells = generate_cells(&config, &proto_data)?;
+        Map::with_cells(config, proto_data, seed, cells)
+    }
+
+    fn with_cells(
+        config: MapConfig,
+        proto_data: Vec<Prototype>,
+        seed: u64,
+        cells: Box<dyn CellStorage<Cell>>,
+    ) -> Result<Self, String> {
+        let chunks = layout_chunks(&config)?;
+        Ok(Self {
+            config,
             cells,
+            pending_chunks: (0..chunks.len()).collect(),
             chunks,
-            chunk_overlap,
-            current_chunk: 0,
+            processed_chunks: vec![],
+            current_chunk: None,
+            requested_chunks: VecDeque::new(),
+            chunk_order: ChunkOrder::Linear,
+            focus: Vector3i::ZERO,
+            chunk_stats: ChunkStats::default(),
+            chunk_started_at: Instant::now(),
+            entropy_queue: EntropyQueue::new(),
+            dirty: BTreeSet::new(),
+            seam_repairs: HashMap::new(),
+            chunk_backup: vec![],
+            chunk_attempts: HashMap::new(),
+            retry_policy: RetryPolicy::default(),
+            escalated_chunks: HashSet::new(),
             proto_data,
+            weight_field: WeightField::default(),
+            disabled: HashSet::new(),
+            tag_constraints: vec![],
+            pinned: HashMap::new(),
+            edit: None,
+            rng: SmallRng::seed_from_u64(seed),
+        })
+    }
+
+    pub fn from_snapshot(
+        snapshot: MapSnapshot,
+        proto_data: Vec<Prototype>,
+        seed: u64,
+    ) -> Result<Self, String> {
+        let config = MapConfig {
+            origin: to_vector(snapshot.origin),
+            size: to_vector(snapshot.size),
+            chunk_size: to_vector(snapshot.chunk_size),
+            chunk_overlap: to_vector(snapshot.chunk_overlap),
+            storage: snapshot.storage,
+            solver: snapshot.solver,
+            fill: snapshot.fill,
+        };
+        // The cells come from the snapshot, so the fill isn't needed to make them
+        let cells = open_cells(&config, &proto_data);
+        let mut map = Map::with_cells(config.clone(), proto_data, seed, cells)?;
+
+        if snapshot.cells.len() != map.cells.len() {
+            return Err(format!(
+                "Snapshot has {} cells, but a map of size {} has {}",
+                snapshot.cells.len(),
+                config.size,
+                map.cells.len()
+            ));
+        }
+
+        // Snapshots store cells in [y][x][z] order from the origin
+        let indices: Vec<usize> = map.cells.indices_in(config.origin, config.size).collect();
+        for (index, ids) in indices.into_iter().zip(snapshot.cells.iter()) {
+            let protos = ids
+                .iter()
+                .map(|id| map.find_prototype(id))
+                .collect::<Result<Vec<Prototype>, String>>()?;
+            let position = map.cells.position_of(index);
+            map.cells.at_mut(index).change(position, &protos);
+        }
+
+        for (position, id) in snapshot.pinned.iter() {
+            let proto = map.find_prototype(id)?;
+            map.pinned.insert(to_vector(*position), proto);
+        }
+
+        map.chunks = snapshot
+            .chunks
+            .iter()
+            .map(|(position, size)| Chunk::new(to_vector(*position), to_vector(*size)))
+            .collect();
+        if let Some(index) = snapshot
+            .processed_chunks
+            .iter()
+            .chain(snapshot.current_chunk.iter())
+            .chain(snapshot.requested_chunks.iter())
+            .find(|index| **index >= map.chunks.len())
+        {
+            return Err(format!(
+                "Snapshot refers to chunk {} which doesn't exist",
+                index
+            ));
+        }
+
+        map.processed_chunks = snapshot.processed_chunks;
+        map.current_chunk = snapshot.current_chunk;
+        map.requested_chunks = snapshot.requested_chunks.into_iter().collect();
+        map.pending_chunks = (0..map.chunks.len())
+            .filter(|index| {
+                !map.processed_chunks.contains(index)
+                    && map.current_chunk != Some(*index)
+                    && !map.requested_chunks.contains(index)
+            })
+            .collect();
+        // A block's backup isn't saved, so a block in progress is started over from what it has now
+        if config.solver == SolverMode::ModifyInBlocks {
+            if let Some(index) = map.current_chunk.take() {
+                map.requested_chunks.push_front(index);
+            }
+        }
+        map.rebuild_entropy_queue();
+        Ok(map)
+    }
+
+    // Modifies a finished map in blocks, starting from its cells. Every cell must be collapsed,
+    //  and fit all of its neighbours.
+    pub fn from_fill(
+        mut snapshot: MapSnapshot,
+        proto_data: Vec<Prototype>,
+        seed: u64,
+    ) -> Result<Self, String> {
+        snapshot.solver = SolverMode::ModifyInBlocks;
+        snapshot.current_chunk = None;
+        let mut map = Map::from_snapshot(snapshot, proto_data, seed)?;
+        map.check_fill()?;
+
+        // Every block starts out pending, whatever the map was doing when it was saved
+        map.chunks = layout_chunks(&map.config)?;
+        map.processed_chunks.clear();
+        map.requested_chunks.clear();
+        map.pending_chunks = (0..map.chunks.len()).collect();
+        Ok(map)
+    }
+
+    fn check_fill(&self) -> Result<(), String> {
+        for index in self.cells.indices() {
+            let position = self.cells.position_of(index);
+            let cell = self.cells.at(index);
+            if cell.possibilities.len() != 1 {
+                return Err(format!(
+                    "Cell {} has {} possibilities, but a fill has to be fully collapsed",
+                    position,
+                    cell.possibilities.len()
+                ));
+            }
+            for neighbor in self.cells.neighbors(index).into_iter().flatten() {
+                let direction = self.cells.position_of(neighbor) - position;
+                if !seams::is_compatible(cell, self.cells.at(neighbor), direction) {
+                    return Err(format!(
+                        "Cells {} and {} of the fill don't fit together",
+                        position,
+                        position + direction
+                    ));
+                }
+            }
         }
+        Ok(())
     }
 
     // called "down" from the collapser
 
-    pub fn initialize(&mut self) -> Option<DriverUpdate> {
+    pub fn initialize(&mut self) -> DriverUpdate {
         self.prepare_next_chunk()
     }
 
     pub fn collapse_next(&mut self) -> Option<DriverUpdate> {
-        let chunk = self.chunks.get(self.current_chunk)?;
-        let changed = chunk.clone().collapse_next(self);
-        return match changed {
+        let chunk = match self.current_chunk {
+            Some(index) => self.chunks[index],
+            None => return Some(self.prepare_next_chunk()),
+        };
+        let changed = chunk.collapse_next(self);
+        match changed {
             Some(changes) => {
                 // godot_print!("chunk collapsed next and got {:?} changes", changes.len());
+                self.chunk_stats.collapses += 1;
+                self.chunk_stats.cells_changed += changes.len() as u32;
                 Some(self.on_cells_changed(changes))
             }
             None => {
-                self.current_chunk += 1;
-                self.prepare_next_chunk()
+                let (finished, restored) = self.finish_current_chunk();
+                let mut update = self.prepare_next_chunk();
+                update.chunk_events.insert(0, finished);
+                // A block that was put back has to be redrawn before whatever comes next
+                if !restored.is_empty() {
+                    let mut changes = DriverUpdate::new_changes(restored);
+                    changes.merge_changes(DriverUpdate::new(None, update.changes.take()));
+                    update.changes = changes.changes;
+                }
+                Some(update)
+            }
+        }
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.current_chunk.is_none()
+            && self.pending_chunks.is_empty()
+            && self.requested_chunks.is_empty()
+    }
+
+    // Takes effect from the next chunk onwards; the chunk in progress is always finished first
+    pub fn set_chunk_order(&mut self, order: ChunkOrder) {
+        self.chunk_order = order;
+    }
+
+    pub fn set_focus(&mut self, focus: Vector3i) {
+        self.focus = focus;
+    }
+
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+    }
+
+    // Applies to every cell that isn't collapsed yet
+    pub fn set_weight_field(&mut self, field: WeightField) -> Result<(), String> {
+        for layer in field.layers.iter() {
+            layer.validate()?;
+            self.select_prototypes(&layer.selector)?;
+        }
+        self.weight_field = field;
+        self.rebuild_entropy_queue();
+        Ok(())
+    }
+
+    // Every cell's current possibilities, for when Godot needs to redraw the whole map
+    pub fn full_update(&self) -> DriverUpdate {
+        let changes = self
+            .cells
+            .indices()
+            .map(|index| CellChange {
+                position: self.cells.position_of(index),
+                new_protos: self.cells.at(index).possibilities.clone(),
+            })
+            .collect();
+        DriverUpdate::new_changes(changes)
+    }
+
+    pub fn pin_cells(&mut self, cells: Vec<(Vector3i, String)>) -> Result<DriverUpdate, String> {
+        // Validate everything up front so that a bad entry doesn't leave the map half-pinned
+        let mut pins = vec![];
+        for (position, id) in cells {
+            if !self.cells.contains(position) {
+                return Err(format!("Cell {} is outside of the map", position));
+            }
+            pins.push((position, self.find_prototype(&id)?));
+        }
+
+        let changes = self.edit("Pinning", |map| {
+            let mut positions = vec![];
+            for (position, proto) in pins.iter() {
+                map.set_possibilities(*position, &vec![proto.clone()]);
+                positions.push(*position);
+            }
+            let whole_map = Chunk::new(map.config.origin, map.config.size);
+            whole_map.propagate_from(positions, map)
+        })?;
+        self.pinned.extend(pins);
+        Ok(DriverUpdate::new_changes(changes))
+    }
+
+    // Queue the given region to be reset and collapsed again. It's treated like any other chunk,
+    //  so its cells are reset in the prepare phase because they overlap previous chunks.
+    pub fn regenerate_region(
+        &mut self,
+        position: Vector3i,
+        size: Vector3i,
+    ) -> Result<DriverUpdate, String> {
+        if size.x <= 0 || size.y <= 0 || size.z <= 0 {
+            return Err(format!("Region size must be positive, got {}", size));
+        }
+
+        let region = Chunk::new(position, size);
+        if region
+            .get_overlapping(&Chunk::new(self.config.origin, self.config.size))
+            .is_empty()
+        {
+            return Err(format!(
+                "Region at {} with size {} is outside of the map",
+                position, size
+            ));
+        }
+
+        let was_completed = self.is_completed();
+        self.chunks.push(region);
+        self.requested_chunks.push_back(self.chunks.len() - 1);
+        if was_completed {
+            return Ok(self.prepare_next_chunk());
+        }
+
+        Ok(DriverUpdate::new(None, None))
+    }
+
+    pub fn set_weights(&mut self, weights: Vec<(PrototypeSelector, f32)>) -> Result<(), String> {
+        for (selector, weight) in weights.iter() {
+            if *weight <= 0.0 {
+                return Err(format!(
+                    "Weight for {} must be positive, got {}",
+                    selector, weight
+                ));
+            }
+            self.select_prototypes(selector)?;
+        }
+
+        let apply = |protos: &mut Vec<Prototype>| {
+            for proto in protos.iter_mut() {
+                let selected = weights.iter().rev().find(|(s, _)| s.matches(proto));
+                if let Some((_, weight)) = selected {
+                    proto.weight = *weight;
+                }
             }
         };
+
+        apply(&mut self.proto_data);
+        let indices: Vec<usize> = self.cells.indices().collect();
+        for index in indices {
+            apply(&mut self.cells.at_mut(index).possibilities);
+        }
+        // With a weight field, cells are queued by their weighted entropy
+        self.rebuild_entropy_queue();
+
+        Ok(())
+    }
+
+    // Cells that are already collapsed keep what they have. Re-enabled prototypes are offered
+    //  to every other cell again, as far as its collapsed neighbours allow.
+    pub fn set_enabled(
+        &mut self,
+        selectors: Vec<PrototypeSelector>,
+        enabled: bool,
+    ) -> Result<DriverUpdate, String> {
+        let mut ids = HashSet::new();
+        for selector in selectors.iter() {
+            ids.extend(self.select_prototypes(selector)?);
+        }
+
+        if enabled {
+            let disabled = self.disabled.clone();
+            self.disabled.retain(|id| !ids.contains(id));
+            let update = self.reopen_cells("Enabling");
+            if update.is_err() {
+                self.disabled = disabled;
+            }
+            return update;
+        }
+
+        let update = self.narrow_open_cells("Disabling", |_, p| !ids.contains(&p.id))?;
+        self.disabled.extend(ids);
+        Ok(update)
+    }
+
+    // Removes the rule's prototypes from open cells in its region, and keeps them out of cells
+    //  that are reset there later
+    pub fn add_tag_constraint(
+        &mut self,
+        constraint: TagConstraint,
+    ) -> Result<DriverUpdate, String> {
+        constraint.validate()?;
+        let update = self.narrow_open_cells("The tag constraint", |position, p| {
+            !constraint.contains(position) || constraint.allows(p)
+        })?;
+        self.tag_constraints.push(constraint);
+        Ok(update)
+    }
+
+    pub fn clear_tag_constraints(&mut self) -> Result<DriverUpdate, String> {
+        let constraints = std::mem::take(&mut self.tag_constraints);
+        let update = self.reopen_cells("Clearing the tag constraints");
+        if update.is_err() {
+            self.tag_constraints = constraints;
+        }
+        update
+    }
+
+    pub fn snapshot(&self) -> MapSnapshot {
+        let cells = self
+            .cells
+            .indices_in(self.config.origin, self.config.size)
+            .map(|index| {
+                let cell = self.cells.at(index);
+                cell.possibilities.iter().map(|p| p.id.clone()).collect()
+            })
+            .collect();
+
+        MapSnapshot {
+            origin: to_array(self.config.origin),
+            size: to_array(self.config.size),
+            chunk_size: to_array(self.config.chunk_size),
+            chunk_overlap: to_array(self.config.chunk_overlap),
+            storage: self.config.storage,
+            solver: self.config.solver,
+            fill: self.config.fill.clone(),
+            chunks: self
+                .chunks
+                .iter()
+                .map(|chunk| (to_array(chunk.position()), to_array(chunk.size())))
+                .collect(),
+            processed_chunks: self.processed_chunks.clone(),
+            current_chunk: self.current_chunk,
+            requested_chunks: self.requested_chunks.iter().copied().collect(),
+            cells,
+            pinned: self
+                .pinned
+                .iter()
+                .map(|(position, proto)| (to_array(*position), proto.id.clone()))
+                .collect(),
+        }
     }
 
     // called "up" from chunks
 
-    pub fn get_cell(&mut self, cell_position: Vector3i) -> Option<&Cell> {
-        self.cells
-            .get(cell_position.y as usize)?
-            .get(cell_position.x as usize)?
-            .get(cell_position.z as usize)
+    pub fn cells(&self) -> &dyn CellStorage<Cell> {
+        self.cells.as_ref()
     }
 
-    pub fn get_cell_mut(&mut self, cell_position: Vector3i) -> Option<&mut Cell> {
-        self.cells
-            .get_mut(cell_position.y as usize)?
-            .get_mut(cell_position.x as usize)?
-            .get_mut(cell_position.z as usize)
+    pub fn get_cell(&self, cell_position: Vector3i) -> Option<&Cell> {
+        self.cells.get(cell_position)
+    }
+
+    pub fn collapse_cell(&mut self, cell_position: Vector3i) -> Option<CellChange> {
+        let index = self.cells.index_of(cell_position)?;
+        let sample = self
+            .weight_field
+            .sample(cell_position, self.config.origin, self.config.size);
+        let change = self.cells.at_mut(index).collapse(
+            cell_position,
+            None,
+            |p| sample.weight(p),
+            &mut self.rng,
+        );
+        self.on_cell_changed(index);
+        change
+    }
+
+    // Select the "lowest entropy" cell of the current chunk, breaking ties at random.
+    // In reality, there are some rules in place to maintain stability that mean that this is often
+    //  not the true lowest-entropy cell.
+    pub fn lowest_entropy_cell(&mut self) -> Option<Vector3i> {
+        let index = self.entropy_queue.pick(&mut self.rng)?;
+        Some(self.cells.position_of(index))
     }
 
+    // Keeps the entropy queue in step with a cell whose possibilities may have just changed
+    pub fn on_cell_changed(&mut self, index: usize) {
+        let Some(current) = self.current_chunk else {
+            return;
+        };
+        let position = self.cells.position_of(index);
+        if self.chunks[current].contains(position) {
+            let entropy = self.entropy_key(index, position);
+            self.entropy_queue.update(index, position, entropy);
+        }
+    }
+
+    // Pinned cells are reset to their pinned prototype rather than to every prototype.
+    // The cell needs constraining by its neighbours again, so they're all marked dirty along with
+    //  it.
     pub fn reset_cell(&mut self, cell_position: Vector3i) -> Option<CellChange> {
-        let all_protos = self.proto_data.clone();
-        self.get_cell_mut(cell_position)?.change(&all_protos)
+        let index = self.cells.index_of(cell_position)?;
+        let protos = self.reset_possibilities(cell_position);
+
+        self.dirty.insert(index);
+        self.dirty
+            .extend(self.cells.neighbors(index).into_iter().flatten());
+        self.set_possibilities(cell_position, &protos)
+    }
+
+    pub fn mark_dirty(&mut self, cell_position: Vector3i) {
+        if let Some(index) = self.cells.index_of(cell_position) {
+            self.dirty.insert(index);
+        }
+    }
+
+    // Positions of every dirty cell, in memory order. The dirty set is cleared.
+    pub fn take_dirty(&mut self) -> Vec<Vector3i> {
+        let dirty = std::mem::take(&mut self.dirty);
+        dirty
+            .into_iter()
+            .map(|index| self.cells.position_of(index))
+            .collect()
+    }
+
+    // Called when propagation would leave a cell with no possibilities
+    pub fn record_contradiction(&mut self, cell_position: Vector3i) {
+        match &mut self.edit {
+            Some(edit) => edit.contradictions.push(cell_position),
+            None => self.chunk_stats.contradictions += 1,
+        }
+    }
+
+    pub fn set_possibilities(
+        &mut self,
+        position: Vector3i,
+        prototypes: &Vec<Prototype>,
+    ) -> Option<CellChange> {
+        let index = self.cells.index_of(position)?;
+        if let Some(edit) = &mut self.edit {
+            let cell = self.cells.at(index);
+            edit.before
+                .entry(index)
+                .or_insert_with(|| cell.possibilities.clone());
+        }
+        let change = self.cells.at_mut(index).change(position, prototypes);
+        self.on_cell_changed(index);
+        change
     }
 
     // private
 
+    fn find_prototype(&self, id: &str) -> Result<Prototype, String> {
+        self.proto_data
+            .iter()
+            .find(|p| p.id == id)
+            .cloned()
+            .ok_or(format!("Unknown prototype '{}'", id))
+    }
+
+    // Ids of every prototype the selector matches, which must be at least one
+    fn select_prototypes(&self, selector: &PrototypeSelector) -> Result<Vec<String>, String> {
+        let ids: Vec<String> = self
+            .proto_data
+            .iter()
+            .filter(|p| selector.matches(p))
+            .map(|p| p.id.clone())
+            .collect();
+        if ids.is_empty() {
+            return Err(format!("No prototypes match {}", selector));
+        }
+        Ok(ids)
+    }
+
+    // Without a weight field this is the number of possibilities. With one it's the weighted
+    //  entropy, kept above 1 so that only collapsed cells leave the queue, so cells where the field
+    //  strongly favours a few prototypes are collapsed first.
+    fn entropy_key(&self, index: usize, position: Vector3i) -> usize {
+        let cell = self.cells.at(index);
+        if self.weight_field.is_empty() || cell.entropy() <= 1 {
+            return cell.entropy();
+        }
+        let sample = self
+            .weight_field
+            .sample(position, self.config.origin, self.config.size);
+        let entropy = cell.weighted_entropy(|p| sample.weight(p));
+        2 + (entropy * ENTROPY_RESOLUTION) as usize
+    }
+
+    // Cells that aren't collapsed or pinned, which are the ones bans and constraints change
+    fn open_cells(&self) -> Vec<Vector3i> {
+        self.cells
+            .indices()
+            .filter(|index| self.cells.at(*index).possibilities.len() > 1)
+            .map(|index| self.cells.position_of(index))
+            .filter(|position| !self.pinned.contains_key(position))
+            .collect()
+    }
+
+    // Resets every open cell, then constrains them by their collapsed neighbours again. Used when
+    //  prototypes become allowed again.
+    fn reopen_cells(&mut self, what: &str) -> Result<DriverUpdate, String> {
+        let changes = self.edit(what, |map| {
+            let mut changes = vec![];
+            for position in map.open_cells() {
+                changes.extend(map.reset_cell(position));
+            }
+            let whole_map = Chunk::new(map.config.origin, map.config.size);
+            changes.append(&mut whole_map.propagate_dirty(map));
+            changes
+        })?;
+        Ok(DriverUpdate::new_changes(changes))
+    }
+
+    // Removes every possibility of an open cell that `keep` rejects. Every cell is checked first,
+    //  and the edit is undone if propagating it empties a cell further away, so the map is never
+    //  left half-changed.
+    fn narrow_open_cells(
+        &mut self,
+        what: &str,
+        keep: impl Fn(Vector3i, &Prototype) -> bool,
+    ) -> Result<DriverUpdate, String> {
+        let mut narrowed = vec![];
+        for position in self.open_cells() {
+            let Some(cell) = self.cells.get(position) else {
+                continue;
+            };
+            let remaining: Vec<Prototype> = cell
+                .possibilities
+                .iter()
+                .filter(|p| keep(position, p))
+                .cloned()
+                .collect();
+            if remaining.is_empty() {
+                return Err(format!(
+                    "{} would leave cell {} with no possibilities",
+                    what, position
+                ));
+            }
+            if remaining.len() != cell.possibilities.len() {
+                narrowed.push((position, remaining));
+            }
+        }
+
+        let changes = self.edit(what, |map| {
+            let mut positions = vec![];
+            for (position, remaining) in narrowed {
+                map.set_possibilities(position, &remaining);
+                positions.push(position);
+            }
+            let whole_map = Chunk::new(map.config.origin, map.config.size);
+            whole_map.propagate_from(positions, map)
+        })?;
+        Ok(DriverUpdate::new_changes(changes))
+    }
+
+    // Runs an edit that propagates across the whole map. The contradictions it runs into are its
+    //  own rather than the current chunk's: if there are any, every cell it changed is put back and
+    //  the first cell it would have emptied is reported.
+    fn edit<T>(&mut self, what: &str, f: impl FnOnce(&mut Self) -> T) -> Result<T, String> {
+        self.edit = Some(EditJournal::default());
+        let result = f(self);
+        let journal = self.edit.take().unwrap_or_default();
+        let Some(empty) = journal.contradictions.first() else {
+            return Ok(result);
+        };
+
+        for (index, protos) in journal.before {
+            let position = self.cells.position_of(index);
+            self.cells.at_mut(index).change(position, &protos);
+            self.on_cell_changed(index);
+        }
+        Err(format!(
+            "{} would leave cell {} with no possibilities",
+            what, empty
+        ))
+    }
+
+    fn select_next_chunk(&mut self) -> Option<usize> {
+        if let Some(index) = self.requested_chunks.pop_front() {
+            return Some(index);
+        }
+
+        let stride = self.config.chunk_size - self.config.chunk_overlap;
+        let index = scheduler::select_next_chunk(
+            &self.chunk_order,
+            self.focus,
+            stride,
+            &self.chunks,
+            &self.pending_chunks,
+        )?;
+        self.pending_chunks.retain(|i| *i != index);
+        Some(index)
+    }
+
     fn on_cells_changed(&mut self, changes: Vec<CellChange>) -> DriverUpdate {
         for change in changes.iter() {
-            let pos = change.position;
-            let cell = &mut self.cells[pos.y as usize][pos.x as usize][pos.z as usize];
-            cell.change(&change.new_protos);
+            self.set_possibilities(change.position, &change.new_protos);
         }
         DriverUpdate::new_changes(changes)
     }
 
-    fn prepare_next_chunk(&mut self) -> Option<DriverUpdate> {
-        if self.current_chunk >= self.chunks.len() {
-            godot_print!("All chunks processed. Stopping.");
-            return Some(DriverUpdate::new_state(CollapserState::STOPPED));
+    fn reset_possibilities(&self, position: Vector3i) -> Vec<Prototype> {
+        match self.pinned.get(&position) {
+            Some(proto) => vec![proto.clone()],
+            None => self
+                .proto_data
+                .iter()
+                .filter(|p| self.is_allowed(position, p))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    // Whether the bans and tag constraints let a cell at this position be offered the prototype
+    fn is_allowed(&self, position: Vector3i, proto: &Prototype) -> bool {
+        !self.disabled.contains(&proto.id)
+            && self
+                .tag_constraints
+                .iter()
+                .all(|c| !c.contains(position) || c.allows(proto))
+    }
+
+    fn rebuild_entropy_queue(&mut self) {
+        self.entropy_queue.clear();
+        let Some(current) = self.current_chunk else {
+            return;
+        };
+
+        let chunk = self.chunks[current];
+        self.entropy_queue.reset(chunk.position(), chunk.size());
+        let indices: Vec<usize> = self
+            .cells
+            .indices_in(chunk.position(), chunk.size())
+            .collect();
+        for index in indices {
+            self.on_cell_changed(index);
         }
+    }
+
+    fn prepare_next_chunk(&mut self) -> DriverUpdate {
+        let Some(index) = self.select_next_chunk() else {
+            log_print!("All chunks processed.");
+            return DriverUpdate::new_state(CollapserState::COMPLETED);
+        };
+        self.current_chunk = Some(index);
+        self.chunk_stats = ChunkStats::default();
+        self.chunk_started_at = Instant::now();
+        self.rebuild_entropy_queue();
+
+        let next_chunk = self.chunks[index];
+        let started = self.chunk_event(ChunkEventKind::Started, &next_chunk);
+        self.chunk_backup = self
+            .cells
+            .indices_in(next_chunk.position(), next_chunk.size())
+            .map(|i| {
+                let possibilities = self.cells.at(i).possibilities.clone();
+                (self.cells.position_of(i), possibilities)
+            })
+            .collect();
+        let mut changes = match self.config.solver {
+            SolverMode::Chunked => self.reset_seams(&next_chunk),
+            SolverMode::ModifyInBlocks => self.reset_block(&next_chunk),
+        };
 
-        let next_chunk = self.chunks.get(self.current_chunk);
+        //changes.append(&mut next_chunk.apply_custom_constraints(self));
+        changes.append(&mut next_chunk.propagate_dirty(self));
+        self.chunk_stats.cells_changed += changes.len() as u32;
+
+        let mut update = DriverUpdate::new_changes(changes);
+        update.chunk_events = vec![
+            started,
+            self.chunk_event(ChunkEventKind::SeamPropagated, &next_chunk),
+        ];
+        update
+    }
+
+    // Resets the cells a new chunk shares with finished chunks. Only the seam needs propagating
+    //  afterwards: the reset cells and their neighbours, plus the cells of finished chunks that
+    //  border this one.
+    fn reset_seams(&mut self, next_chunk: &Chunk) -> Vec<CellChange> {
         let mut overlapping: Vec<Vector3i> = Vec::new();
         let mut neighboring: Vec<Vector3i> = Vec::new();
-        match next_chunk {
-            None => unreachable!(),
-            Some(next) => {
-                for i in 0..self.current_chunk {
-                    if let Some(other) = self.chunks.get(i) {
-                        overlapping.append(&mut next.get_overlapping(other));
-                        neighboring.append(&mut next.get_neighbors(other, self.chunk_overlap));
-                    }
-                }
+        // Pull in constraints from as deep as the widest overlap
+        let overlap = self.config.chunk_overlap;
+        let seam_depth = overlap.x.max(overlap.y).max(overlap.z);
+        for i in self.processed_chunks.iter() {
+            if let Some(other) = self.chunks.get(*i) {
+                overlapping.append(&mut next_chunk.get_overlapping(other));
+                neighboring.append(&mut next_chunk.get_neighbors(other, seam_depth));
             }
         }
 
         let mut changes = vec![];
-
-        let next_chunk = self.chunks[self.current_chunk];
         for cell in overlapping.iter() {
             if let Some(change) = self.reset_cell(*cell) {
                 changes.push(change);
             }
         }
+        for cell in neighboring.iter() {
+            self.mark_dirty(*cell);
+        }
 
-        //changes.append(&mut next_chunk.apply_custom_constraints(self));
-        changes.append(&mut next_chunk.propagate_all(self));
+        changes
+    }
+
+    // Resets every cell of a block. The cells around the block are already valid, so only they
+    //  need propagating in. Propagating from the reset cells themselves would only find that
+    //  anything goes.
+    fn reset_block(&mut self, block: &Chunk) -> Vec<CellChange> {
+        let indices: Vec<usize> = self
+            .cells
+            .indices_in(block.position(), block.size())
+            .collect();
+
+        let mut changes = vec![];
+        for index in indices {
+            let position = self.cells.position_of(index);
+            for neighbor in self.cells.neighbors(index).into_iter().flatten() {
+                if !block.contains(self.cells.position_of(neighbor)) {
+                    self.dirty.insert(neighbor);
+                }
+            }
+
+            let protos = self.reset_possibilities(position);
+            if let Some(change) = self.set_possibilities(position, &protos) {
+                changes.push(change);
+            }
+        }
+
+        changes
+    }
+
+    // Moves the current chunk to processed and reports how it went, along with any cells that were
+    //  put back as they were
+    fn finish_current_chunk(&mut self) -> (ChunkEvent, Vec<CellChange>) {
+        let index = self
+            .current_chunk
+            .take()
+            .expect("finished a chunk without a current chunk");
+        self.entropy_queue.clear();
+        self.chunk_stats.attempts = self.chunk_attempts.get(&index).copied().unwrap_or(0) + 1;
+
+        if self.config.solver == SolverMode::ModifyInBlocks {
+            return self.finish_block(index);
+        }
+
+        let chunk = self.chunks[index];
+        if self.chunk_stats.contradictions > 0 {
+            if let Some(retried) = self.retry_chunk(index) {
+                return retried;
+            }
+        }
+
+        self.chunk_backup.clear();
+        self.chunk_attempts.remove(&index);
+        let violations = self.repair_seams(&chunk);
+        self.processed_chunks.push(index);
+        self.chunk_stats.seam_violations = violations.len() as u32;
+
+        let kind = if self.chunk_stats.contradictions > 0 || !violations.is_empty() {
+            ChunkEventKind::Failed
+        } else {
+            ChunkEventKind::Completed
+        };
+        let mut event = self.chunk_event(kind, &chunk);
+        event.violations = violations;
+        (event, vec![])
+    }
+
+    // Puts a failed chunk back as it was before it started and queues it to run again with a new
+    //  seed. Once it's out of retries, it's escalated according to the retry policy instead.
+    // Returns None if the failure should be accepted as it is.
+    fn retry_chunk(&mut self, index: usize) -> Option<(ChunkEvent, Vec<CellChange>)> {
+        let chunk = self.chunks[index];
+        let attempts = self.chunk_stats.attempts;
+
+        if attempts > self.retry_policy.max_retries {
+            let replacements = self.escalate(index)?;
+            log_print!(
+                "chunk at {} failed {} times, escalating to {} chunks",
+                chunk.position(),
+                attempts,
+                replacements.len()
+            );
+
+            // The old chunk counts as processed, but everything in it is reset by its replacements
+            self.chunk_attempts.remove(&index);
+            self.processed_chunks.push(index);
+            for replacement in replacements.into_iter().rev() {
+                self.chunks.push(replacement);
+                self.escalated_chunks.insert(self.chunks.len() - 1);
+                self.requested_chunks.push_front(self.chunks.len() - 1);
+            }
+        } else {
+            self.chunk_attempts.insert(index, attempts);
+            self.requested_chunks.push_front(index);
+        }
+
+        self.rng = SmallRng::seed_from_u64(self.rng.gen());
+        let restored = self.restore_backup();
+        Some((self.chunk_event(ChunkEventKind::Retried, &chunk), restored))
+    }
+
+    // The chunks to collapse in place of one that ran out of retries, if any
+    fn escalate(&self, index: usize) -> Option<Vec<Chunk>> {
+        if self.escalated_chunks.contains(&index) {
+            return None;
+        }
 
-        changes.append(&mut next_chunk.propagate_from(neighboring, self));
-        changes.append(&mut next_chunk.propagate_all(self));
+        let chunk = self.chunks[index];
 
-        Some(DriverUpdate::new_changes(changes))
+        let replacements = match self.retry_policy.escalation {
+            Escalation::Accept => return None,
+            Escalation::Shrink => chunk.octants(),
+            Escalation::WidenReset => vec![chunk
+                .grown(self.config.chunk_overlap)
+                .clipped_to(&Chunk::new(self.config.origin, self.config.size))],
+        };
+
+        // A single cell can't shrink, and there's nothing to widen by without any overlap
+        if replacements.len() == 1
+            && replacements[0].position() == chunk.position()
+            && replacements[0].size() == chunk.size()
+        {
+            return None;
+        }
+        Some(replacements)
+    }
+
+    // A block is only kept if it fits everything around it. Otherwise its old cells are put back,
+    //  which were valid to begin with, and it's tried again later with a new seed.
+    fn finish_block(&mut self, index: usize) -> (ChunkEvent, Vec<CellChange>) {
+        let block = self.chunks[index];
+        let mut violations = seams::find_violations(self.cells.as_ref(), &block, |_| true);
+        self.chunk_stats.seam_violations = violations.len() as u32;
+
+        if self.chunk_stats.contradictions == 0 && violations.is_empty() {
+            self.chunk_backup.clear();
+            self.chunk_attempts.remove(&index);
+            self.processed_chunks.push(index);
+            return (self.chunk_event(ChunkEventKind::Completed, &block), vec![]);
+        }
+
+        let restored = self.restore_backup();
+        let attempts = self.chunk_stats.attempts;
+        let retrying = attempts <= self.retry_policy.max_retries;
+        if retrying {
+            self.chunk_attempts.insert(index, attempts);
+            self.pending_chunks.push(index);
+            self.rng = SmallRng::seed_from_u64(self.rng.gen());
+        } else {
+            log_print!("giving up on block at {}", block.position());
+            self.chunk_attempts.remove(&index);
+            self.processed_chunks.push(index);
+        }
+
+        for violation in violations.iter_mut() {
+            violation.repairing = retrying;
+        }
+        let kind = if retrying {
+            ChunkEventKind::Retried
+        } else {
+            ChunkEventKind::Failed
+        };
+        let mut event = self.chunk_event(kind, &block);
+        event.violations = violations;
+        (event, restored)
+    }
+
+    // Puts the current chunk's cells back as they were before it was reset. Collapsed cells keep
+    //  their number of possibilities, so every restored cell is reported as changed.
+    fn restore_backup(&mut self) -> Vec<CellChange> {
+        let backup = std::mem::take(&mut self.chunk_backup);
+        let mut restored = vec![];
+        for (position, protos) in backup {
+            let protos = self.restorable(position, protos);
+            self.set_possibilities(position, &protos);
+            restored.push(CellChange {
+                position,
+                new_protos: protos,
+            });
+        }
+
+        restored
     }
+
+    // A backed up cell as it has to be put back, given the bans, tag constraints and pins made since
+    //  the backup was taken. Pinned cells go back to their pin, and open cells lose what's been
+    //  banned, but collapsed cells are kept as they are, as they would be by the ban itself. Cells
+    //  that differ from their backup are left dirty, to constrain their neighbours.
+    fn restorable(&mut self, position: Vector3i, protos: Vec<Prototype>) -> Vec<Prototype> {
+        let restored = match self.pinned.get(&position) {
+            Some(pin) => vec![pin.clone()],
+            None if protos.len() <= 1 => return protos,
+            None => {
+                let allowed: Vec<Prototype> = protos
+                    .iter()
+                    .filter(|p| self.is_allowed(position, p))
+                    .cloned()
+                    .collect();
+                // Opened up again if nothing's left, like any other reset cell
+                if allowed.is_empty() {
+                    self.reset_possibilities(position)
+                } else {
+                    allowed
+                }
+            }
+        };
+
+        if restored != protos {
+            self.mark_dirty(position);
+        }
+        restored
+    }
+
+    // Checks a finished chunk's boundary against the chunks finished before it, and queues a small
+    //  block around each mismatched pair to be re-solved. Repairs go through the same prepare phase
+    //  as any other chunk, so the block is reset and constrained by the cells around it.
+    fn repair_seams(&mut self, chunk: &Chunk) -> Vec<SeamViolation> {
+        let chunks = &self.chunks;
+        let processed = &self.processed_chunks;
+        let mut violations = seams::find_violations(self.cells.as_ref(), chunk, |position| {
+            processed.iter().any(|i| chunks[*i].contains(position))
+        });
+
+        let map_bounds = Chunk::new(self.config.origin, self.config.size);
+        let mut blocks: Vec<Chunk> = vec![];
+        for violation in violations.iter_mut() {
+            // One block often covers several neighbouring violations
+            if blocks
+                .iter()
+                .any(|b| b.contains(violation.position) && b.contains(violation.neighbor))
+            {
+                violation.repairing = true;
+                continue;
+            }
+
+            let repairs = self.seam_repairs.entry(violation.position).or_insert(0);
+            if *repairs >= MAX_SEAM_REPAIRS {
+                log_print!(
+                    "giving up on seam between {} and {}",
+                    violation.position,
+                    violation.neighbor
+                );
+                continue;
+            }
+
+            *repairs += 1;
+            violation.repairing = true;
+            blocks.push(seams::repair_block(
+                violation,
+                SEAM_REPAIR_RADIUS,
+                &map_bounds,
+            ));
+        }
+
+        for block in blocks {
+            self.chunks.push(block);
+            self.requested_chunks.push_back(self.chunks.len() - 1);
+        }
+
+        violations
+    }
+
+    fn chunk_event(&self, kind: ChunkEventKind, chunk: &Chunk) -> ChunkEvent {
+        let mut stats = self.chunk_stats;
+        stats.elapsed_ms = self.chunk_started_at.elapsed().as_millis() as u64;
+        ChunkEvent {
+            kind,
+            position: chunk.position(),
+            size: chunk.size(),
+            stats,
+            violations: vec![],
+        }
+    }
+}
+
+fn to_array(v: Vector3i) -> [i32; 3] {
+    [v.x, v.y, v.z]
+}
+
+fn to_vector(v: [i32; 3]) -> Vector3i {
+    Vector3i {
+        x: v[0],
+        y: v[1],
+        z: v[2],
+    }
+}
+
+fn layout_chunks(config: &MapConfig) -> Result<Vec<Chunk>, String> {
+    // The layout works in map-local coordinates
+    Ok(
+        ChunkLayout::new(config.size, config.chunk_size, config.chunk_overlap)?
+            .chunks()
+            .into_iter()
+            .map(|chunk| Chunk::new(config.origin + chunk.position(), chunk.size()))
+            .collect(),
+    )
+}
+
+fn generate_cells(
+    config: &MapConfig,
+    all_protos: &Vec<Prototype>,
+) -> Result<Box<dyn CellStorage<Cell>>, String> {
+    if config.solver == SolverMode::ModifyInBlocks {
+        let fill = fill_prototype(config, all_protos)?;
+        return Ok(new_storage(
+            config.storage,
+            config.origin,
+            config.size,
+            |_position| Cell::new(vec![fill.clone()]),
+        ));
+    }
+    Ok(open_cells(config, all_protos))
 }
 
-fn generate_cells(size: Vector3i, all_protos: &Vec<Prototype>) -> Vec<Vec<Vec<Cell>>> {
+// The prototype every cell starts out as when modifying in blocks. Filling the map with it has to
+//  be valid, so it must fit next to itself along every axis the map has more than one cell on.
+fn fill_prototype(config: &MapConfig, all_protos: &[Prototype]) -> Result<Prototype, String> {
+    let Some(id) = &config.fill else {
+        return Err(
+            "Modifying in blocks needs a fill prototype, or a finished map loaded as the fill"
+                .into(),
+        );
+    };
+    let fill = all_protos
+        .iter()
+        .find(|p| &p.id == id)
+        .cloned()
+        .ok_or(format!("Unknown fill prototype '{}'", id))?;
+
+    let axes = [
+        ("x", config.size.x, Vector3i::RIGHT),
+        ("y", config.size.y, Vector3i::UP),
+        ("z", config.size.z, Vector3i::BACK),
+    ];
+    for (axis, size, direction) in axes {
+        let fits = fill.compatible_with(fill.id.clone(), direction)
+            && fill.compatible_with(fill.id.clone(), -direction);
+        if size > 1 && !fits {
+            return Err(format!(
+                "Fill prototype '{}' can't sit next to itself along {}",
+                fill.id, axis
+            ));
+        }
+    }
+    Ok(fill)
+}
+
+// Every cell open to every prototype
+fn open_cells(config: &MapConfig, all_protos: &Vec<Prototype>) -> Box<dyn CellStorage<Cell>> {
     // let uncapped_x_min = Prototype::uncapped(all_protos, Vector3i::LEFT);
     // let uncapped_x_max = Prototype::uncapped(all_protos, Vector3i::RIGHT);
     // let uncapped_y_min = Prototype::uncapped(all_protos, Vector3i::DOWN);
@@ -135,76 +1207,30 @@ fn generate_cells(size: Vector3i, all_protos: &Vec<Prototype>) -> Vec<Vec<Vec<Ce
     // let uncapped_z_max = Prototype::uncapped(all_protos, Vector3i::BACK);
     // let not_bot = Prototype::not_constrained(all_protos, "BOT".into());
 
-    let mut cells = vec![];
-    for y in 0..size.y {
-        let mut plane = vec![];
-        for x in 0..size.x {
-            let mut row = vec![];
-            for z in 0..size.z {
-                let cell_protos = all_protos.clone();
-
-                // if x == 0 {
-                //     Prototype::retain_uncapped(&mut cell_protos, Vector3i::LEFT);
-                // } else if x == size.x - 1 {
-                //     Prototype::retain_uncapped(&mut cell_protos, Vector3i::RIGHT);
-                // }
-
-                // if y == 0 {
-                //     Prototype::retain_uncapped(&mut cell_protos, Vector3i::DOWN);
-                // } else {
-                //     Prototype::retain_not_constrained(&mut cell_protos, "BOT".into());
-                //     if y == size.y - 1 {
-                //         Prototype::retain_uncapped(&mut cell_protos, Vector3i::UP);
-                //     }
-                // }
-
-                // if z == 0 {
-                //     Prototype::retain_uncapped(&mut cell_protos, Vector3i::FORWARD);
-                // } else if z == size.z - 1 {
-                //     Prototype::retain_uncapped(&mut cell_protos, Vector3i::BACK);
-                // }
-
-                let cell = Cell::new(Vector3i { x, y, z }, cell_protos);
-                row.push(cell);
-            }
-            plane.push(row);
-        }
-        cells.push(plane);
-    }
-    cells
-}
+    new_storage(config.storage, config.origin, config.size, |_position| {
+        let cell_protos = all_protos.clone();
 
-fn generate_chunks(map_size: Vector3i, chunk_size: Vector3i, chunk_overlap: i32) -> Vec<Chunk> {
-    let num_x = if chunk_size.x <= chunk_overlap {
-        1
-    } else {
-        ceili(((map_size.x + chunk_overlap) / (chunk_size.x - chunk_overlap)) as f64) as i32
-    };
+        // if position.x == 0 {
+        //     Prototype::retain_uncapped(&mut cell_protos, Vector3i::LEFT);
+        // } else if position.x == config.size.x - 1 {
+        //     Prototype::retain_uncapped(&mut cell_protos, Vector3i::RIGHT);
+        // }
 
-    let num_y = if chunk_size.y <= chunk_overlap {
-        1
-    } else {
-        ceili(((map_size.y + chunk_overlap) / (chunk_size.y - chunk_overlap)) as f64) as i32
-    };
-
-    let num_z = if chunk_size.z <= chunk_overlap {
-        1
-    } else {
-        ceili(((map_size.z + chunk_overlap) / (chunk_size.z - chunk_overlap)) as f64) as i32
-    };
-
-    let position_factor = chunk_size - Vector3i::ONE * chunk_overlap;
+        // if position.y == 0 {
+        //     Prototype::retain_uncapped(&mut cell_protos, Vector3i::DOWN);
+        // } else {
+        //     Prototype::retain_not_constrained(&mut cell_protos, "BOT".into());
+        //     if position.y == config.size.y - 1 {
+        //         Prototype::retain_uncapped(&mut cell_protos, Vector3i::UP);
+        //     }
+        // }
 
-    let mut chunks = vec![];
-    for y in 0..num_y {
-        for x in 0..num_x {
-            for z in 0..num_z {
-                let position = position_factor * Vector3i { x, y, z };
-                let new_chunk = Chunk::new(position, chunk_size);
-                chunks.push(new_chunk);
-            }
-        }
-    }
+        // if position.z == 0 {
+        //     Prototype::retain_uncapped(&mut cell_protos, Vector3i::FORWARD);
+        // } else if position.z == config.size.z - 1 {
+        //     Prototype::retain_uncapped(&mut cell_protos, Vector3i::BACK);
+        // }
 
-    chunks
+        Cell::new(cell_protos)
+    })
 }
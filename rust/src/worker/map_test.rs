@@ -0,0 +1,259 @@
+#[cfg(test)]
+mod tests {
+    use godot::builtin::Vector3i;
+
+    use crate::{
+        models::{
+            chunk_event::ChunkEventKind,
+            map_config::{MapConfig, SolverMode, StorageKind},
+            map_snapshot::MapSnapshot,
+            prototype::Prototype,
+            prototype_selector::PrototypeSelector,
+            test_fixtures,
+        },
+        worker::map::Map,
+    };
+
+    fn v(x: i32, y: i32, z: i32) -> Vector3i {
+        Vector3i { x, y, z }
+    }
+
+    // A prototype that may sit next to the given ids around it, and above and below it
+    fn proto(id: &str, weight: f32, horizontal: &[&str], vertical: &[&str]) -> Prototype {
+        let (h, v) = (horizontal, vertical);
+        test_fixtures::proto(id, weight, [h, h, h, h, v, v])
+    }
+
+    fn blocks_config(size: Vector3i, fill: Option<&str>) -> MapConfig {
+        MapConfig {
+            origin: Vector3i::ZERO,
+            size,
+            chunk_size: v(3, 1, 3),
+            chunk_overlap: Vector3i::ZERO,
+            storage: StorageKind::Dense,
+            solver: SolverMode::ModifyInBlocks,
+            fill: fill.map(String::from),
+        }
+    }
+
+    fn chunked_config(size: Vector3i) -> MapConfig {
+        MapConfig {
+            chunk_size: size,
+            solver: SolverMode::Chunked,
+            ..blocks_config(size, None)
+        }
+    }
+
+    // Collapses until the map is done, returning the kind of every chunk event on the way
+    fn run(map: &mut Map) -> Vec<ChunkEventKind> {
+        let mut kinds = vec![];
+        for _ in 0..1000 {
+            let update = map.collapse_next().unwrap();
+            kinds.extend(update.chunk_events.iter().map(|event| event.kind));
+            if map.is_completed() {
+                return kinds;
+            }
+        }
+        panic!("map never completed");
+    }
+
+    #[test]
+    fn test_retries_a_chunk_that_contradicts_itself() {
+        // Nothing fits next to anything, so collapsing either cell leaves the other with nothing
+        let protos = vec![proto("a", 1.0, &[], &[]), proto("b", 1.0, &[], &[])];
+        let mut map = Map::new(chunked_config(v(2, 1, 1)), protos, 3).unwrap();
+
+        let finished: Vec<ChunkEventKind> = run(&mut map)
+            .into_iter()
+            .filter(|kind| {
+                !matches!(
+                    kind,
+                    ChunkEventKind::Started | ChunkEventKind::SeamPropagated
+                )
+            })
+            .collect();
+        // Two retries, and then the default policy accepts the failure
+        let expected = vec![
+            ChunkEventKind::Retried,
+            ChunkEventKind::Retried,
+            ChunkEventKind::Failed,
+        ];
+        assert_eq!(expected, finished);
+    }
+
+    #[test]
+    fn test_retries_keep_bans_made_during_the_chunk() {
+        // "a", "b" and "c" swap "a" and "b" along x, and "b" and "c" along z. Going around a square
+        //  either way ends up at a different prototype, so the square always contradicts itself.
+        let swap = |id: &str, pair: [&str; 2]| -> Vec<String> {
+            let swapped = match pair.iter().position(|p| *p == id) {
+                Some(i) => pair[1 - i],
+                None => id,
+            };
+            vec![swapped.to_string(), "x".to_string()]
+        };
+        let mut protos: Vec<Prototype> = ["a", "b", "c"]
+            .into_iter()
+            .map(|id| {
+                let mut proto = proto(id, 1.0, &[], &[]);
+                let (x, z) = (swap(id, ["a", "b"]), swap(id, ["b", "c"]));
+                proto.valid_neighbors = vec![x.clone(), z.clone(), x, z, vec![], vec![]];
+                proto
+            })
+            .collect();
+        // Until it's banned, "x" fits anywhere and would be picked almost every time
+        protos.push(proto("x", 1000.0, &["a", "b", "c", "x"], &[]));
+
+        let mut map = Map::new(chunked_config(v(2, 1, 2)), protos, 5).unwrap();
+        map.initialize();
+        map.set_enabled(vec![PrototypeSelector::Id("x".into())], false)
+            .unwrap();
+
+        let kinds = run(&mut map);
+        assert!(kinds.contains(&ChunkEventKind::Retried));
+        for index in map.cells().indices() {
+            let cell = map.cells().at(index);
+            assert!(cell.possibilities.iter().all(|p| p.id != "x"));
+        }
+    }
+
+    #[test]
+    fn test_pin_contradictions_are_not_the_chunks() {
+        // Each prototype only fits next to itself
+        let protos = vec![proto("a", 1.0, &["a"], &[]), proto("b", 1.0, &["b"], &[])];
+        let mut map = Map::new(chunked_config(v(2, 1, 1)), protos, 0).unwrap();
+        map.initialize();
+
+        map.pin_cells(vec![(v(0, 0, 0), "a".into())]).unwrap();
+        let error = map.pin_cells(vec![(v(1, 0, 0), "b".into())]).unwrap_err();
+        assert!(error.contains("(0, 0, 0)"), "{}", error);
+
+        // The failed pin is undone, and the chunk finishes without any contradictions
+        let ids = |map: &Map| -> Vec<String> {
+            let cell = map.get_cell(v(1, 0, 0)).unwrap();
+            cell.possibilities.iter().map(|p| p.id.clone()).collect()
+        };
+        assert_eq!(vec!["a"], ids(&map));
+        assert_eq!(1, map.snapshot().pinned.len());
+        assert!(!run(&mut map).contains(&ChunkEventKind::Retried));
+    }
+
+    #[test]
+    fn test_ban_contradictions_are_not_the_chunks() {
+        // Banning "p" leaves both cells as "s", which can't sit next to itself. "s" is never picked
+        //  while "p" is allowed.
+        let protos = vec![proto("p", 1.0, &["p"], &[]), proto("s", 0.0, &[], &[])];
+        let mut map = Map::new(chunked_config(v(2, 1, 1)), protos, 0).unwrap();
+        map.initialize();
+
+        let ban = vec![PrototypeSelector::Id("p".into())];
+        assert!(map.set_enabled(ban, false).is_err());
+
+        let cell = map.get_cell(v(1, 0, 0)).unwrap();
+        assert_eq!(2, cell.possibilities.len());
+        assert!(!run(&mut map).contains(&ChunkEventKind::Retried));
+    }
+
+    #[test]
+    fn test_fill_must_fit_itself() {
+        let protos = vec![
+            proto("flat", 1.0, &["flat", "other"], &[]),
+            proto("other", 1.0, &["flat"], &["other"]),
+        ];
+        let flat = v(4, 1, 4);
+        let tall = v(4, 2, 4);
+
+        let tests = [
+            ("no fill", flat, None, false),
+            ("unknown fill", flat, Some("missing"), false),
+            ("fits itself", flat, Some("flat"), true),
+            ("doesn't fit itself around it", flat, Some("other"), false),
+            ("doesn't fit itself above it", tall, Some("flat"), false),
+            ("one cell across", v(1, 2, 1), Some("other"), true),
+        ];
+        for (name, size, fill, ok) in tests {
+            let map = Map::new(blocks_config(size, fill), protos.clone(), 0);
+            assert_eq!(ok, map.is_ok(), "Test Failed: {}", name);
+        }
+    }
+
+    #[test]
+    fn test_modify_in_blocks_changes_a_block() {
+        // Anything goes, but the fill is far less likely to be picked than the other prototype. The
+        //  blocks don't overlap, so the next one doesn't reset any of the finished one.
+        let protos = vec![
+            proto("fill", 1.0, &["fill", "grass"], &["fill", "grass"]),
+            proto("grass", 100.0, &["fill", "grass"], &["fill", "grass"]),
+        ];
+        let mut map = Map::new(blocks_config(v(6, 1, 6), Some("fill")), protos, 7).unwrap();
+
+        let mut completed = None;
+        for _ in 0..1000 {
+            let update = map.collapse_next().unwrap();
+            completed = update
+                .chunk_events
+                .into_iter()
+                .find(|event| event.kind == ChunkEventKind::Completed);
+            if completed.is_some() {
+                break;
+            }
+        }
+        let block = completed.expect("no block was completed");
+
+        let ids: Vec<&str> = map
+            .cells()
+            .indices_in(block.position, block.size)
+            .map(|index| {
+                let possibilities = &map.cells().at(index).possibilities;
+                assert_eq!(1, possibilities.len());
+                possibilities[0].id.as_str()
+            })
+            .collect();
+        assert!(ids.contains(&"grass"), "block wasn't changed: {:?}", ids);
+    }
+
+    #[test]
+    fn test_finished_map_as_fill() {
+        // Neither prototype fits next to itself, so only a finished map can start the blocks
+        let protos = vec![
+            proto("black", 1.0, &["white"], &[]),
+            proto("white", 1.0, &["black"], &[]),
+        ];
+        let size = v(4, 1, 4);
+        assert!(Map::new(blocks_config(size, Some("black")), protos.clone(), 0).is_err());
+
+        // Snapshots store cells in [y][x][z] order
+        let checkerboard: Vec<Vec<String>> = (0..4)
+            .flat_map(|x| (0..4).map(move |z| (x + z) % 2))
+            .map(|parity| vec![["black", "white"][parity].to_string()])
+            .collect();
+        let snapshot = |cells: Vec<Vec<String>>| MapSnapshot {
+            origin: [0, 0, 0],
+            size: [4, 1, 4],
+            chunk_size: [4, 1, 4],
+            chunk_overlap: [0, 0, 0],
+            storage: StorageKind::Dense,
+            solver: SolverMode::Chunked,
+            fill: None,
+            chunks: vec![([0, 0, 0], [4, 1, 4])],
+            processed_chunks: vec![0],
+            current_chunk: None,
+            requested_chunks: vec![],
+            cells,
+            pinned: vec![],
+        };
+
+        let map = Map::from_fill(snapshot(checkerboard.clone()), protos.clone(), 0).unwrap();
+        assert_eq!(SolverMode::ModifyInBlocks, map.config.solver);
+        assert!(!map.is_completed());
+
+        let mut mismatched = checkerboard.clone();
+        mismatched[5] = vec!["white".into()];
+        let mut open = checkerboard;
+        open[5] = vec!["black".into(), "white".into()];
+        for (name, cells) in [("mismatched", mismatched), ("not collapsed", open)] {
+            let map = Map::from_fill(snapshot(cells), protos.clone(), 0);
+            assert!(map.is_err(), "Test Failed: {}", name);
+        }
+    }
+}
@@ -1,6 +1,22 @@
+pub(crate) mod block_grid;
 pub(crate) mod cell;
 pub(crate) mod chunk;
+pub(crate) mod chunk_layout;
 pub(crate) mod collapser;
+pub(crate) mod entropy_queue;
+pub(crate) mod grid;
 pub(crate) mod map;
+pub(crate) mod outbox;
+pub(crate) mod scheduler;
+pub(crate) mod seams;
+pub(crate) mod storage;
 
+mod block_grid_test;
+mod chunk_layout_test;
 mod chunk_test;
+mod entropy_queue_test;
+mod grid_test;
+mod map_test;
+mod outbox_test;
+mod scheduler_test;
+mod seams_test;
@@ -0,0 +1,57 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{SyncSender, TrySendError};
+
+use crate::models::driver_update::DriverUpdate;
+
+// Buffers updates that don't fit in the bounded channel to the driver.
+// Consecutive change batches are merged so that, while Godot is catching up, the backlog is at most
+//  one entry per cell rather than one entry per collapse.
+pub struct Outbox {
+    sender: SyncSender<DriverUpdate>,
+    pending: VecDeque<DriverUpdate>,
+}
+
+impl Outbox {
+    pub fn new(sender: SyncSender<DriverUpdate>) -> Self {
+        Self {
+            sender,
+            pending: VecDeque::new(),
+        }
+    }
+
+    // Returns false once the driver has hung up
+    pub fn post(&mut self, update: DriverUpdate) -> bool {
+        match self.pending.back_mut() {
+            Some(last) if last.is_changes_only() && update.is_changes_only() => {
+                last.merge_changes(update)
+            }
+            _ => self.pending.push_back(update),
+        }
+
+        self.flush()
+    }
+
+    // Send as much of the backlog as the channel will take, in order.
+    // Returns false once the driver has hung up
+    pub fn flush(&mut self) -> bool {
+        while let Some(update) = self.pending.pop_front() {
+            match self.sender.try_send(update) {
+                Ok(()) => (),
+                Err(TrySendError::Full(update)) => {
+                    self.pending.push_front(update);
+                    return true;
+                }
+                Err(TrySendError::Disconnected(_)) => {
+                    self.pending.clear();
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
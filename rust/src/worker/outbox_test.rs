@@ -0,0 +1,99 @@
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::sync_channel;
+
+    use godot::builtin::Vector3i;
+
+    use crate::models::{
+        collapser_state::CollapserState,
+        driver_update::{CellChangeGodot, DriverUpdate},
+    };
+    use crate::worker::outbox::Outbox;
+
+    fn changes(changes: Vec<(i32, &str)>) -> DriverUpdate {
+        DriverUpdate::new(
+            None,
+            Some(
+                changes
+                    .into_iter()
+                    .map(|(x, protos)| CellChangeGodot {
+                        position: Vector3i { x, y: 0, z: 0 },
+                        new_protos: protos.into(),
+                    })
+                    .collect(),
+            ),
+        )
+    }
+
+    fn summarize(update: &DriverUpdate) -> Vec<(i32, String)> {
+        update
+            .changes
+            .iter()
+            .flatten()
+            .map(|c| (c.position.x, c.new_protos.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn test_merges_changes_while_full() {
+        let (sender, receiver) = sync_channel(1);
+        let mut outbox = Outbox::new(sender);
+
+        assert!(outbox.post(changes(vec![(0, "p0,p1")])));
+        assert!(outbox.is_empty(), "first update should fit in the channel");
+
+        assert!(outbox.post(changes(vec![(0, "p0"), (1, "p1,p2")])));
+        assert!(outbox.post(changes(vec![(1, "p2"), (2, "p3")])));
+        assert!(!outbox.is_empty());
+
+        let first = receiver.try_recv().unwrap();
+        assert_eq!(vec![(0, "p0,p1".to_string())], summarize(&first));
+
+        assert!(outbox.flush());
+        assert!(outbox.is_empty());
+
+        let merged = receiver.try_recv().unwrap();
+        assert_eq!(
+            vec![
+                (0, "p0".to_string()),
+                (1, "p2".to_string()),
+                (2, "p3".to_string())
+            ],
+            summarize(&merged)
+        );
+    }
+
+    #[test]
+    fn test_keeps_order_around_other_updates() {
+        let (sender, receiver) = sync_channel(1);
+        let mut outbox = Outbox::new(sender);
+
+        outbox.post(changes(vec![(0, "p0")]));
+        outbox.post(changes(vec![(1, "p1")]));
+        outbox.post(DriverUpdate::new_state(CollapserState::IDLE));
+        outbox.post(changes(vec![(2, "p2")]));
+
+        let mut received = vec![];
+        while !outbox.is_empty() || received.len() < 4 {
+            match receiver.try_recv() {
+                Ok(update) => received.push(update),
+                Err(_) => assert!(outbox.flush()),
+            }
+        }
+
+        assert_eq!(vec![(0, "p0".to_string())], summarize(&received[0]));
+        assert_eq!(vec![(1, "p1".to_string())], summarize(&received[1]));
+        assert_eq!(Some(CollapserState::IDLE), received[2].new_state);
+        assert_eq!(vec![(2, "p2".to_string())], summarize(&received[3]));
+    }
+
+    #[test]
+    fn test_reports_disconnect() {
+        let (sender, receiver) = sync_channel(1);
+        let mut outbox = Outbox::new(sender);
+        drop(receiver);
+
+        assert!(!outbox.post(changes(vec![(0, "p0")])));
+        assert!(outbox.is_empty());
+    }
+}
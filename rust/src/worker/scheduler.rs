@@ -0,0 +1,68 @@
+use godot::prelude::*;
+
+use crate::models::chunk_order::ChunkOrder;
+
+use super::chunk::Chunk;
+
+// Pick the next chunk out of `pending` (indices into `chunks`) according to the given order.
+// Ties are always broken by index, so every strategy falls back to the linear order.
+pub fn select_next_chunk(
+    order: &ChunkOrder,
+    focus: Vector3i,
+    stride: Vector3i,
+    chunks: &[Chunk],
+    pending: &[usize],
+) -> Option<usize> {
+    pending
+        .iter()
+        .copied()
+        .filter(|index| *index < chunks.len())
+        .map(|index| (chunk_key(order, focus, stride, &chunks[index]), index))
+        .min_by(|(a, a_index), (b, b_index)| {
+            a.0.cmp(&b.0)
+                .then(a.1.total_cmp(&b.1))
+                .then(a_index.cmp(b_index))
+        })
+        .map(|(_, index)| index)
+}
+
+// Sort key for a chunk; lower goes first
+fn chunk_key(order: &ChunkOrder, focus: Vector3i, stride: Vector3i, chunk: &Chunk) -> (i64, f32) {
+    let offset = chunk.center() - focus;
+    match order {
+        ChunkOrder::Linear => (0, 0.0),
+        ChunkOrder::Spiral => {
+            // Rings are measured in chunks rather than cells so that each ring is one chunk wide,
+            //  and chunks within a ring are swept by angle around the focus
+            let ring = [
+                ring_distance(offset.x, stride.x),
+                ring_distance(offset.y, stride.y),
+                ring_distance(offset.z, stride.z),
+            ]
+            .into_iter()
+            .max()
+            .unwrap_or(0);
+            let angle = (offset.z as f32).atan2(offset.x as f32);
+            (ring, angle)
+        }
+        ChunkOrder::NearestToFocus => {
+            let distance_squared =
+                (offset.x as i64).pow(2) + (offset.y as i64).pow(2) + (offset.z as i64).pow(2);
+            (distance_squared, 0.0)
+        }
+        ChunkOrder::Custom { priorities } => {
+            let priority = priorities
+                .iter()
+                .filter(|(position, _)| chunk.contains(*position))
+                .map(|(_, priority)| *priority)
+                .max()
+                .unwrap_or(0);
+            (-(priority as i64), 0.0)
+        }
+    }
+}
+
+fn ring_distance(offset: i32, stride: i32) -> i64 {
+    let stride = stride.max(1) as f32;
+    (offset.abs() as f32 / stride).round() as i64
+}
@@ -0,0 +1,120 @@
+#[cfg(test)]
+mod tests {
+    use godot::builtin::Vector3i;
+
+    use crate::models::chunk_order::ChunkOrder;
+    use crate::worker::{chunk::Chunk, scheduler::select_next_chunk};
+
+    const STRIDE: Vector3i = Vector3i { x: 3, y: 1, z: 3 };
+
+    // 5x5 grid of 3x1x3 chunks with no overlap, in x-major order
+    fn grid() -> Vec<Chunk> {
+        let mut chunks = vec![];
+        for x in 0..5 {
+            for z in 0..5 {
+                chunks.push(Chunk::new(
+                    Vector3i {
+                        x: x * 3,
+                        y: 0,
+                        z: z * 3,
+                    },
+                    Vector3i { x: 3, y: 1, z: 3 },
+                ));
+            }
+        }
+        chunks
+    }
+
+    // Drain every chunk in the order the scheduler picks them
+    fn drain(order: &ChunkOrder, focus: Vector3i, chunks: &[Chunk]) -> Vec<usize> {
+        let mut pending: Vec<usize> = (0..chunks.len()).collect();
+        let mut picked = vec![];
+        while let Some(index) = select_next_chunk(order, focus, STRIDE, chunks, &pending) {
+            pending.retain(|i| *i != index);
+            picked.push(index);
+        }
+        picked
+    }
+
+    fn ring(chunk: &Chunk, focus: Vector3i) -> i32 {
+        let offset = chunk.center() - focus;
+        (offset.x.abs() / STRIDE.x).max(offset.z.abs() / STRIDE.z)
+    }
+
+    #[test]
+    fn test_linear_keeps_generation_order() {
+        let chunks = grid();
+        let focus = Vector3i { x: 7, y: 0, z: 7 };
+        let picked = drain(&ChunkOrder::Linear, focus, &chunks);
+        assert_eq!((0..chunks.len()).collect::<Vec<_>>(), picked);
+    }
+
+    #[test]
+    fn test_nearest_to_focus_starts_at_focus() {
+        let chunks = grid();
+        let focus = Vector3i { x: 13, y: 0, z: 1 };
+        let picked = drain(&ChunkOrder::NearestToFocus, focus, &chunks);
+
+        assert!(chunks[picked[0]].contains(focus));
+        assert_eq!(chunks.len(), picked.len());
+        let distance = |index: usize| {
+            let offset = chunks[index].center() - focus;
+            offset.x * offset.x + offset.y * offset.y + offset.z * offset.z
+        };
+        for pair in picked.windows(2) {
+            assert!(distance(pair[0]) <= distance(pair[1]));
+        }
+    }
+
+    #[test]
+    fn test_spiral_finishes_each_ring_before_the_next() {
+        let chunks = grid();
+        let focus = Vector3i { x: 7, y: 0, z: 7 };
+        let picked = drain(&ChunkOrder::Spiral, focus, &chunks);
+
+        assert!(chunks[picked[0]].contains(focus));
+        let rings: Vec<i32> = picked.iter().map(|i| ring(&chunks[*i], focus)).collect();
+        assert_eq!(vec![0; 1], rings[..1]);
+        assert_eq!(vec![1; 8], rings[1..9]);
+        assert_eq!(vec![2; 16], rings[9..]);
+    }
+
+    #[test]
+    fn test_custom_priorities() {
+        let chunks = grid();
+        let order = ChunkOrder::Custom {
+            priorities: vec![
+                (Vector3i { x: 14, y: 0, z: 14 }, 5),
+                (Vector3i { x: 0, y: 0, z: 14 }, 10),
+                (Vector3i { x: 7, y: 0, z: 7 }, -1),
+            ],
+        };
+        let picked = drain(&order, Vector3i::ZERO, &chunks);
+
+        assert_eq!(4, picked[0], "highest priority first");
+        assert_eq!(24, picked[1]);
+        assert_eq!(0, picked[2], "unlisted chunks keep their linear order");
+        assert_eq!(12, picked[picked.len() - 1], "negative priorities go last");
+    }
+
+    #[test]
+    fn test_ignores_chunks_that_are_not_pending() {
+        let chunks = grid();
+        let pending = vec![3, 17];
+        let focus = Vector3i::ZERO;
+        assert_eq!(
+            Some(3),
+            select_next_chunk(
+                &ChunkOrder::NearestToFocus,
+                focus,
+                STRIDE,
+                &chunks,
+                &pending
+            )
+        );
+        assert_eq!(
+            None,
+            select_next_chunk(&ChunkOrder::Linear, focus, STRIDE, &chunks, &[])
+        );
+    }
+}
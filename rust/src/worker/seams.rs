@@ -0,0 +1,67 @@
+use godot::prelude::*;
+
+use crate::models::chunk_event::SeamViolation;
+
+use super::{cell::Cell, chunk::Chunk, storage::CellStorage};
+
+// Every adjacent pair with one cell inside `chunk` and the other in a finished cell outside of it
+//  that fails `is_compatible`. Violations are in [y][x][z] order of the cell inside the chunk.
+pub fn find_violations<F: Fn(Vector3i) -> bool>(
+    cells: &dyn CellStorage<Cell>,
+    chunk: &Chunk,
+    is_finished: F,
+) -> Vec<SeamViolation> {
+    let mut violations = vec![];
+    for index in cells.indices_in(chunk.position(), chunk.size()) {
+        let position = cells.position_of(index);
+        for neighbor_index in cells.neighbors(index).into_iter().flatten() {
+            let neighbor = cells.position_of(neighbor_index);
+            if chunk.contains(neighbor) || !is_finished(neighbor) {
+                continue;
+            }
+
+            if !is_compatible(
+                cells.at(index),
+                cells.at(neighbor_index),
+                neighbor - position,
+            ) {
+                violations.push(SeamViolation {
+                    position,
+                    neighbor,
+                    repairing: false,
+                });
+            }
+        }
+    }
+
+    violations
+}
+
+// Whether some possibility of `cell` allows some possibility of `neighbor` in `direction`, and the
+//  other way around. Adjacency lists aren't guaranteed to be symmetric, so both sides are checked.
+pub fn is_compatible(cell: &Cell, neighbor: &Cell, direction: Vector3i) -> bool {
+    cell.possibilities
+        .iter()
+        .any(|proto| proto.compatible_with_any(&neighbor.possibilities, direction))
+        && neighbor
+            .possibilities
+            .iter()
+            .any(|proto| proto.compatible_with_any(&cell.possibilities, -direction))
+}
+
+// The block to re-solve for a violation: both cells plus `radius` cells around them, clipped to
+//  `bounds`
+pub fn repair_block(violation: &SeamViolation, radius: i32, bounds: &Chunk) -> Chunk {
+    let low = Vector3i {
+        x: violation.position.x.min(violation.neighbor.x) - radius,
+        y: violation.position.y.min(violation.neighbor.y) - radius,
+        z: violation.position.z.min(violation.neighbor.z) - radius,
+    };
+    let high = Vector3i {
+        x: violation.position.x.max(violation.neighbor.x) + radius + 1,
+        y: violation.position.y.max(violation.neighbor.y) + radius + 1,
+        z: violation.position.z.max(violation.neighbor.z) + radius + 1,
+    };
+
+    Chunk::new(low, high - low).clipped_to(bounds)
+}
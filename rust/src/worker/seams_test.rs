@@ -0,0 +1,102 @@
+#[cfg(test)]
+mod tests {
+    use godot::builtin::Vector3i;
+
+    use crate::{
+        models::{chunk_event::SeamViolation, prototype::Prototype, test_fixtures},
+        worker::{cell::Cell, chunk::Chunk, grid::Grid, seams},
+    };
+
+    fn v(x: i32, y: i32, z: i32) -> Vector3i {
+        Vector3i { x, y, z }
+    }
+
+    // A prototype that may only sit next to the given ids, in every direction
+    fn proto(id: &str, neighbors: &[&str]) -> Prototype {
+        test_fixtures::proto(id, 1.0, [neighbors; 6])
+    }
+
+    #[test]
+    fn test_is_compatible() {
+        let grass = proto("grass", &["grass", "sand"]);
+        let sand = proto("sand", &["grass", "sand", "water"]);
+        let water = proto("water", &["sand", "water"]);
+        let one_way = proto("one_way", &["grass"]);
+
+        let cell = |protos: Vec<&Prototype>| Cell::new(protos.into_iter().cloned().collect());
+        let tests = [
+            ("same", cell(vec![&grass]), cell(vec![&grass]), true),
+            ("allowed", cell(vec![&grass]), cell(vec![&sand]), true),
+            ("not allowed", cell(vec![&grass]), cell(vec![&water]), false),
+            (
+                "one of several",
+                cell(vec![&grass, &sand]),
+                cell(vec![&water]),
+                true,
+            ),
+            ("empty", cell(vec![]), cell(vec![&grass]), false),
+            // grass doesn't list one_way, even though one_way lists grass
+            (
+                "asymmetric",
+                cell(vec![&one_way]),
+                cell(vec![&grass]),
+                false,
+            ),
+        ];
+
+        for (name, a, b, expected) in tests {
+            assert_eq!(
+                expected,
+                seams::is_compatible(&a, &b, Vector3i::RIGHT),
+                "Test Failed: {}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_find_violations() {
+        let grass = proto("grass", &["grass", "sand"]);
+        let water = proto("water", &["water"]);
+
+        // A 4x1x1 row: grass, grass | water, grass. The chunk is the left half.
+        let cells = Grid::new(v(0, 0, 0), v(4, 1, 1), |position| match position.x {
+            2 => Cell::new(vec![water.clone()]),
+            _ => Cell::new(vec![grass.clone()]),
+        });
+        let chunk = Chunk::new(v(0, 0, 0), v(2, 1, 1));
+
+        let violations = seams::find_violations(&cells, &chunk, |_| true);
+        assert_eq!(
+            vec![SeamViolation {
+                position: v(1, 0, 0),
+                neighbor: v(2, 0, 0),
+                repairing: false,
+            }],
+            violations
+        );
+
+        // Cells that aren't finished yet don't count
+        let violations = seams::find_violations(&cells, &chunk, |_| false);
+        assert_eq!(Vec::<SeamViolation>::new(), violations);
+    }
+
+    #[test]
+    fn test_repair_block() {
+        let bounds = Chunk::new(v(0, 0, 0), v(10, 10, 10));
+        let violation = |position, neighbor| SeamViolation {
+            position,
+            neighbor,
+            repairing: true,
+        };
+
+        let block = seams::repair_block(&violation(v(4, 4, 4), v(5, 4, 4)), 1, &bounds);
+        assert_eq!(v(3, 3, 3), block.position());
+        assert_eq!(v(4, 3, 3), block.size());
+
+        // Clipped to the map
+        let block = seams::repair_block(&violation(v(0, 9, 4), v(0, 8, 4)), 2, &bounds);
+        assert_eq!(v(0, 6, 2), block.position());
+        assert_eq!(v(3, 4, 5), block.size());
+    }
+}
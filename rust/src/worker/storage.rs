@@ -0,0 +1,54 @@
+use godot::prelude::*;
+
+use crate::models::map_config::StorageKind;
+
+use super::{block_grid::BlockGrid, grid::Grid};
+
+// Storage for one value per cell, addressed either by position or by an opaque index.
+// Indices are only meaningful to the storage that handed them out, and stay valid for its lifetime.
+pub trait CellStorage<T> {
+    fn len(&self) -> usize;
+
+    fn index_of(&self, position: Vector3i) -> Option<usize>;
+
+    fn position_of(&self, index: usize) -> Vector3i;
+
+    fn at(&self, index: usize) -> &T;
+
+    fn at_mut(&mut self, index: usize) -> &mut T;
+
+    // Neighbour indices in grid::DIRECTIONS order
+    fn neighbors(&self, index: usize) -> [Option<usize>; 6];
+
+    // Indices of every stored cell in the given box, in [y][x][z] order
+    fn indices_in(
+        &self,
+        position: Vector3i,
+        size: Vector3i,
+    ) -> Box<dyn Iterator<Item = usize> + '_>;
+
+    // Indices of every stored cell, in no particular order
+    fn indices(&self) -> Box<dyn Iterator<Item = usize> + '_>;
+
+    fn contains(&self, position: Vector3i) -> bool {
+        self.index_of(position).is_some()
+    }
+
+    fn get(&self, position: Vector3i) -> Option<&T> {
+        let index = self.index_of(position)?;
+        Some(self.at(index))
+    }
+}
+
+// Fill the box at `origin` with `size` using the given backend
+pub fn new_storage<T: 'static, F: FnMut(Vector3i) -> T>(
+    kind: StorageKind,
+    origin: Vector3i,
+    size: Vector3i,
+    f: F,
+) -> Box<dyn CellStorage<T>> {
+    match kind {
+        StorageKind::Dense => Box::new(Grid::new(origin, size, f)),
+        StorageKind::Blocks => Box::new(BlockGrid::new(origin, size, f)),
+    }
+}